@@ -18,17 +18,131 @@ pub enum AlertLevel {
     Red,
 }
 
-/// A container for 24 contiguous hours of activity data.
+#[cfg(feature = "sql")]
+impl sqlx::postgres::PgHasArrayType for AlertLevel {
+    fn array_type_info() -> sqlx::postgres::PgTypeInfo {
+        sqlx::postgres::PgTypeInfo::with_name("_alert_level_enum")
+    }
+}
+
+impl std::str::FromStr for AlertLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "green" => Ok(Self::Green),
+            "yellow" => Ok(Self::Yellow),
+            "amber" => Ok(Self::Amber),
+            "red" => Ok(Self::Red),
+            other => Err(format!("{other} is not a valid alert level")),
+        }
+    }
+}
+
+/// What a user is alerted on.
+#[cfg_attr(feature = "sql", derive(sqlx::Type))]
+#[derive(Clone, Copy, Debug, Default, Display, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(
+    feature = "sql",
+    sqlx(type_name = "alert_criterion_enum", rename_all = "snake_case")
+)]
+pub enum AlertCriterion {
+    /// Alert whenever the alert level reaches the user's threshold.
+    #[default]
+    #[display(fmt = "alert level")]
+    AlertLevel,
+    /// Alert whenever the visibility score at any of the user's locations
+    /// reaches their minimum score.
+    #[display(fmt = "visibility score")]
+    VisibilityScore,
+}
+
+/// The minimum activity, in nT, at which each alert level applies.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Thresholds {
+    pub green: f32,
+    pub yellow: f32,
+    pub amber: f32,
+    pub red: f32,
+}
+
+impl Thresholds {
+    /// Derive the alert level for the given activity.
+    pub fn alert_level(&self, value: f32) -> AlertLevel {
+        if value >= self.red {
+            AlertLevel::Red
+        } else if value >= self.amber {
+            AlertLevel::Amber
+        } else if value >= self.yellow {
+            AlertLevel::Yellow
+        } else {
+            AlertLevel::Green
+        }
+    }
+}
+
+impl Default for Thresholds {
+    /// The thresholds published by AuroraWatch UK, for use wherever the upstream
+    /// source doesn't provide its own.
+    fn default() -> Self {
+        Self {
+            green: 0.,
+            yellow: 50.,
+            amber: 100.,
+            red: 200.,
+        }
+    }
+}
+
+/// The station whose activity data is shown when none is specified.
+pub const DEFAULT_STATION: &str = "AWN/LAN1";
+
+/// A container for a contiguous series of activity data recorded at a single
+/// station.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ActivityData {
-    pub activities: [ActivityDataPoint; 24],
+    pub station: String,
+    pub activities: Vec<ActivityDataPoint>,
     pub updated_at: DateTimeUtc,
+    /// The thresholds reported by the station, if any.
+    #[serde(default)]
+    pub thresholds: Option<Thresholds>,
+}
+
+/// How activity data points are combined over a time range.
+#[derive(Clone, Copy, Debug, Display, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Aggregation {
+    /// One data point per hour.
+    #[default]
+    #[display(fmt = "hourly")]
+    Hourly,
+    /// The maximum activity within each day.
+    #[display(fmt = "daily_max")]
+    DailyMax,
+    /// The maximum activity within each week.
+    #[display(fmt = "weekly_max")]
+    WeeklyMax,
+}
+
+impl Aggregation {
+    /// The length of time covered by each data point.
+    pub fn bucket_duration(&self) -> chrono::Duration {
+        match self {
+            Self::Hourly => chrono::Duration::hours(1),
+            Self::DailyMax => chrono::Duration::days(1),
+            Self::WeeklyMax => chrono::Duration::weeks(1),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ActivityDataPoint {
     pub timestamp: DateTimeUtc,
     pub value: f32,
+    /// The alert level reported by the upstream source for this data point.
+    pub alert_level: AlertLevel,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
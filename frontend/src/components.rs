@@ -1,4 +1,5 @@
 pub mod chart;
+pub mod forecast;
 pub mod forms;
 mod header;
 
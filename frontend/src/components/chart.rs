@@ -1,5 +1,7 @@
 mod controls;
 mod plot;
+mod range;
+mod stations;
 
 use chrono::Timelike;
 pub use plot::Plot;
@@ -8,8 +10,10 @@ use yew_hooks::use_async;
 use yew_hooks::use_interval;
 
 use self::controls::Controls;
-use crate::common::ActivityData;
-use crate::services::charts::{self, ActivityDataExt};
+use self::range::RangePicker;
+use self::stations::StationPicker;
+use crate::common::{ActivityData, DEFAULT_STATION};
+use crate::services::charts::{self, ActivityDataExt, ChartRange};
 use crate::theme::ThemeMode;
 
 #[function_component(ActivityChart)]
@@ -21,13 +25,22 @@ pub fn activity_chart() -> Html {
         let now = chrono::Utc::now();
         now.date().and_hms(now.time().hour(), 0, 0)
     });
+    let selected_station_handle = use_state(|| DEFAULT_STATION.to_string());
+    let selected_range_handle = use_state(|| ChartRange::Day);
     let chart_data_handle = use_state_eq(|| None::<ActivityData>);
 
     let fetch_chart_data = {
         let selected_hour = *selected_hour_handle;
+        let selected_station = (*selected_station_handle).clone();
+        let selected_range = *selected_range_handle;
         use_async(async move {
-            log::debug!("fetching activity data; end = {:?}", selected_hour.clone());
-            charts::get_activity_data(selected_hour).await
+            log::debug!(
+                "fetching activity data; end = {:?}, range = {:?}, station = {:?}",
+                selected_hour,
+                selected_range,
+                selected_station
+            );
+            charts::get_activity_data(selected_hour, selected_range, selected_station).await
         })
     };
 
@@ -43,13 +56,15 @@ pub fn activity_chart() -> Html {
 
     {
         let fetch_chart_data = fetch_chart_data.clone();
-        let selected_hour_handle = selected_hour_handle.clone();
+        let selected_hour = *selected_hour_handle;
+        let selected_station = (*selected_station_handle).clone();
+        let selected_range = *selected_range_handle;
         use_effect_with_deps(
             move |_| {
                 fetch_chart_data.run();
                 || ()
             },
-            selected_hour_handle,
+            (selected_hour, selected_station, selected_range),
         );
     }
 
@@ -74,6 +89,10 @@ pub fn activity_chart() -> Html {
                 if let Some(data) = (*chart_data_handle).clone() {
                     html! {
                         <>
+                            <div id="activity-chart-pickers" class="row justify-content-center gx-2 mb-3">
+                                <StationPicker selected_station_handle={selected_station_handle.clone()} />
+                                <RangePicker selected_range_handle={selected_range_handle.clone()} />
+                            </div>
                             <Plot id={plot_id.clone()} plot={data.to_plot(theme_mode)} />
                             <Controls selected_hour_handle={selected_hour_handle.clone()} />
                         </>
@@ -0,0 +1,50 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::services::charts::ChartRange;
+
+#[derive(Properties, PartialEq)]
+pub struct RangePickerProps {
+    pub selected_range_handle: UseStateHandle<ChartRange>,
+}
+
+#[function_component(RangePicker)]
+pub fn range_picker(props: &RangePickerProps) -> Html {
+    log::debug!("render range picker");
+    let RangePickerProps {
+        selected_range_handle,
+    } = props;
+
+    let oninput = {
+        let selected_range_handle = selected_range_handle.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            if let Some(range) = ChartRange::ALL
+                .into_iter()
+                .find(|range| range.label() == el.value())
+            {
+                selected_range_handle.set(range);
+            }
+        })
+    };
+
+    let selected_range = **selected_range_handle;
+
+    html! {
+        <div class="col-auto">
+            <div class={classes!("form-floating")}>
+                <select {oninput} id="activity-chart-range" class={classes!("form-select")}>
+                    {
+                        ChartRange::ALL
+                            .into_iter()
+                            .map(|range| html! {
+                                <option value={range.label()} selected={range == selected_range}>{range.label()}</option>
+                            })
+                            .collect::<Html>()
+                    }
+                </select>
+                <label for="activity-chart-range" class={classes!("form-label")}>{"Range"}</label>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,51 @@
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+
+use crate::services::stations::get_stations;
+
+#[derive(Properties, PartialEq)]
+pub struct StationPickerProps {
+    pub selected_station_handle: UseStateHandle<String>,
+}
+
+#[function_component(StationPicker)]
+pub fn station_picker(props: &StationPickerProps) -> Html {
+    log::debug!("render station picker");
+    let StationPickerProps {
+        selected_station_handle,
+    } = props;
+
+    let stations = yew_hooks::use_async_with_options(
+        async move { get_stations().await },
+        yew_hooks::UseAsyncOptions::enable_auto(),
+    );
+
+    let oninput = {
+        let selected_station_handle = selected_station_handle.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            selected_station_handle.set(el.value());
+        })
+    };
+
+    let selected_station = (**selected_station_handle).clone();
+
+    html! {
+        <div class="col-auto">
+            <div class={classes!("form-floating")}>
+                <select {oninput} id="activity-chart-station" class={classes!("form-select")}>
+                    {
+                        stations.data
+                            .iter()
+                            .flatten()
+                            .map(|station| html! {
+                                <option value={station.clone()} selected={*station == selected_station}>{station}</option>
+                            })
+                            .collect::<Html>()
+                    }
+                </select>
+                <label for="activity-chart-station" class={classes!("form-label")}>{"Station"}</label>
+            </div>
+        </div>
+    }
+}
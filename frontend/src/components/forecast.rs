@@ -0,0 +1,120 @@
+use std::ops::Deref;
+
+use web_sys::HtmlInputElement;
+use yew::prelude::*;
+use yew_hooks::use_async;
+
+use crate::services::forecasts::get_forecast;
+use crate::services::locations::get_locations;
+use crate::types::forecasts::ForecastPoint;
+
+#[function_component(CloudCoverForecast)]
+pub fn cloud_cover_forecast() -> Html {
+    log::debug!("render cloud cover forecast");
+    let location = use_state_eq(String::new);
+    let selected_location_id = use_state_eq(|| None::<i64>);
+
+    let datalist_locations = {
+        let location = location.clone();
+        use_async(async move { get_locations(location.deref().clone()).await })
+    };
+
+    let forecast = {
+        let selected_location_id = *selected_location_id;
+        use_async(async move {
+            match selected_location_id {
+                Some(location_id) => get_forecast(location_id).await,
+                None => Ok(vec![]),
+            }
+        })
+    };
+
+    let oninput = {
+        let location = location.clone();
+        let datalist_locations = datalist_locations.clone();
+        Callback::from(move |e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            location.set(input.value());
+            datalist_locations.run();
+        })
+    };
+
+    // Only select a location once its name exactly matches one of the search results.
+    if let Some(locations) = &datalist_locations.data {
+        if let Some(location_id) = locations.get(&*location) {
+            selected_location_id.set(Some(*location_id));
+        }
+    }
+
+    {
+        let forecast = forecast.clone();
+        use_effect_with_deps(
+            move |_| {
+                forecast.run();
+                || ()
+            },
+            *selected_location_id,
+        );
+    }
+
+    html! {
+        <>
+            <div class={classes!("form-floating", "mb-3")}>
+                <input {oninput} id="forecast-location" class="form-control" list="forecast-location-options" placeholder="Search for a location..." />
+                <label for="forecast-location" class="form-label">{"Search for a location..."}</label>
+                <datalist id="forecast-location-options">
+                    {
+                        datalist_locations.data
+                            .iter()
+                            .flatten()
+                            .map(|(name, id)| html! {<option data-value={id.to_string()} value={name.clone()} />})
+                            .collect::<Html>()
+                    }
+                </datalist>
+            </div>
+            {
+                match (&forecast.data, *selected_location_id) {
+                    (Some(points), Some(_)) if !points.is_empty() => html! {
+                        <ForecastTable points={points.clone()} />
+                    },
+                    (Some(_), Some(_)) => html! {
+                        <p>{"There is no forecast for this location yet. Forecasts are only kept for locations which someone has subscribed to."}</p>
+                    },
+                    _ => html! {},
+                }
+            }
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ForecastTableProps {
+    points: Vec<ForecastPoint>,
+}
+
+#[function_component(ForecastTable)]
+fn forecast_table(props: &ForecastTableProps) -> Html {
+    html! {
+        <table class={classes!("table", "table-striped")}>
+            <thead>
+                <tr>
+                    <th>{"Time"}</th>
+                    <th>{"Cloud cover"}</th>
+                </tr>
+            </thead>
+            <tbody>
+                {
+                    props.points
+                        .iter()
+                        .map(|point| html! {
+                            <tr>
+                                <td>{point.timestamp.with_timezone(&chrono::Local).format("%a %H:%M").to_string()}</td>
+                                <td>{format!("{}%", point.cloud_cover)}</td>
+                            </tr>
+                        })
+                        .collect::<Html>()
+                }
+            </tbody>
+        </table>
+    }
+}
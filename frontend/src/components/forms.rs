@@ -1,5 +1,9 @@
 mod form;
+mod manage;
 mod registration;
+mod verification;
 
-use form::Form;
+use form::{Form, FormStatus};
+pub use manage::{ManageForm, ManageLinkForm};
 pub use registration::RegistrationForm;
+pub use verification::ResendVerificationForm;
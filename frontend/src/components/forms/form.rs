@@ -14,3 +14,28 @@ pub fn form(props: &FormProps) -> Html {
         </form>
     }
 }
+
+#[derive(Properties, PartialEq)]
+pub struct FormStatusProps {
+    pub succeeded: bool,
+    pub failed: bool,
+    pub message: String,
+}
+
+/// Report the outcome of submitting a form.
+#[function_component(FormStatus)]
+pub fn form_status(props: &FormStatusProps) -> Html {
+    if props.succeeded {
+        html! {
+            <div class="alert alert-success" role="alert">{&props.message}</div>
+        }
+    } else if props.failed {
+        html! {
+            <div class="alert alert-danger" role="alert">
+                {"There seems to have been an error - check that you have entered your details correctly and consider trying again later."}
+            </div>
+        }
+    } else {
+        html! {}
+    }
+}
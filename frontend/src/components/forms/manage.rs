@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use yew::prelude::*;
+use yew_hooks::use_async;
+
+use super::registration::{
+    AlertCooldownField, AlertCriterionField, EmailField, LocationsField, MaxCloudCoverField,
+    MinVisibilityScoreField, NotifyAllClearField, QuietHoursField, TimezoneField,
+};
+use super::{Form, FormStatus};
+use crate::services::user::{request_manage_link, update_locations, update_preferences};
+use crate::types::user::{UserLocationsBody, UserPreferencesBody, UserSettings};
+
+#[function_component(ManageLinkForm)]
+pub fn manage_link_form() -> Html {
+    let email_handler = use_state(String::new);
+
+    let manage_link = {
+        let email = email_handler.deref().clone();
+        use_async(async move { request_manage_link(email).await })
+    };
+
+    let onsubmit = {
+        let manage_link = manage_link.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            manage_link.run();
+        })
+    };
+
+    let valid_form = !manage_link.loading && !email_handler.is_empty();
+
+    html! {
+        <>
+            <FormStatus
+                succeeded={manage_link.data.is_some()}
+                failed={manage_link.error.is_some()}
+                message={"If that email address is subscribed, you'll shortly receive an email with a link to manage your subscription."}
+            />
+            <Form {onsubmit}>
+                <EmailField handler={email_handler} />
+                <button disabled={!valid_form} type="submit" class={classes!("btn", "btn-primary", "mb-3")}>{"Email me a link"}</button>
+            </Form>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub struct ManageFormProps {
+    pub token: String,
+    pub settings: UserSettings,
+}
+
+#[function_component(ManageForm)]
+pub fn manage_form(props: &ManageFormProps) -> Html {
+    let settings = &props.settings;
+    let alert_criterion_handler = use_state(|| settings.alert_criterion.clone());
+    let min_visibility_score_handler = use_state(|| settings.min_visibility_score);
+    let max_cloud_cover_handler = use_state(|| settings.max_cloud_cover);
+    let timezone_handler = use_state(|| settings.timezone.clone());
+    let quiet_hours_start_handler = use_state(|| {
+        settings
+            .quiet_hours
+            .as_ref()
+            .map(|quiet_hours| quiet_hours.start.clone())
+    });
+    let quiet_hours_end_handler = use_state(|| {
+        settings
+            .quiet_hours
+            .as_ref()
+            .map(|quiet_hours| quiet_hours.end.clone())
+    });
+    let alert_cooldown_minutes_handler = use_state(|| settings.alert_cooldown_minutes);
+    let notify_all_clear_handler = use_state(|| settings.notify_all_clear);
+    let locations_handler = use_state(|| {
+        settings
+            .locations
+            .iter()
+            .map(|location| (location.name.clone(), location.to_register_location()))
+            .collect::<HashMap<_, _>>()
+    });
+
+    let preferences = UserPreferencesBody {
+        token: props.token.clone(),
+        alert_criterion: alert_criterion_handler.deref().clone(),
+        min_visibility_score: *min_visibility_score_handler,
+        max_cloud_cover: *max_cloud_cover_handler,
+        timezone: timezone_handler.deref().clone(),
+        quiet_hours_start: quiet_hours_start_handler.deref().clone(),
+        quiet_hours_end: quiet_hours_end_handler.deref().clone(),
+        alert_cooldown_minutes: *alert_cooldown_minutes_handler,
+        notify_all_clear: *notify_all_clear_handler,
+    };
+
+    let locations = UserLocationsBody {
+        token: props.token.clone(),
+        locations: locations_handler
+            .deref()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>(),
+    };
+
+    let preferences_update = {
+        let preferences = preferences.clone();
+        use_async(async move { update_preferences(preferences).await })
+    };
+
+    let locations_update = {
+        let locations = locations.clone();
+        use_async(async move { update_locations(locations).await })
+    };
+
+    let onsubmit_preferences = {
+        let preferences_update = preferences_update.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            preferences_update.run();
+        })
+    };
+
+    let onsubmit_locations = {
+        let locations_update = locations_update.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            locations_update.run();
+        })
+    };
+
+    let valid_preferences = !preferences_update.loading && preferences.is_valid();
+    let valid_locations = !locations_update.loading && !locations.locations.is_empty();
+
+    html! {
+        <>
+            <h5 class="mb-3">{"Preferences"}</h5>
+            <FormStatus
+                succeeded={preferences_update.data.is_some()}
+                failed={preferences_update.error.is_some()}
+                message={"Your preferences have been saved."}
+            />
+            <Form onsubmit={onsubmit_preferences}>
+                <AlertCriterionField handler={alert_criterion_handler.clone()} />
+                {
+                    if *alert_criterion_handler == "visibility_score" {
+                        html! { <MinVisibilityScoreField handler={min_visibility_score_handler} /> }
+                    } else {
+                        html! {}
+                    }
+                }
+                <MaxCloudCoverField handler={max_cloud_cover_handler} />
+                <TimezoneField handler={timezone_handler} />
+                <QuietHoursField start_handler={quiet_hours_start_handler} end_handler={quiet_hours_end_handler} />
+                <AlertCooldownField handler={alert_cooldown_minutes_handler} />
+                <NotifyAllClearField handler={notify_all_clear_handler} />
+                <button disabled={!valid_preferences} type="submit" class={classes!("btn", "btn-primary", "mb-3")}>{"Save preferences"}</button>
+            </Form>
+            <h5 class="mt-5 mb-3">{"Locations"}</h5>
+            <FormStatus
+                succeeded={locations_update.data.is_some()}
+                failed={locations_update.error.is_some()}
+                message={"Your locations have been saved."}
+            />
+            <Form onsubmit={onsubmit_locations}>
+                <LocationsField handler={locations_handler} show_thresholds={*alert_criterion_handler == "alert_level"} />
+                <button disabled={!valid_locations} type="submit" class={classes!("btn", "btn-primary", "mb-3")}>{"Save locations"}</button>
+            </Form>
+        </>
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::ops::Deref;
 
+use wasm_bindgen::JsValue;
 use web_sys::HtmlInputElement;
 use yew::prelude::*;
 use yew_hooks::use_async;
@@ -8,18 +9,32 @@ use yew_hooks::use_async;
 use super::Form;
 use crate::routes::LinkHome;
 use crate::services::locations::get_locations;
-use crate::services::user::register;
-use crate::types::user::UserRegisterBody;
+use crate::services::user::{register, resend_verification};
+use crate::types::user::{UserRegisterBody, UserRegisterLocation};
 
 #[function_component(RegistrationForm)]
 pub fn registration_form() -> Html {
     let email_handler = use_state(String::new);
-    let alert_threshold_handler = use_state(|| "yellow".to_string());
-    let locations_handler = use_state(HashMap::<String, i64>::new);
+    let alert_criterion_handler = use_state(|| "alert_level".to_string());
+    let min_visibility_score_handler = use_state(|| 50);
+    let max_cloud_cover_handler = use_state(|| 100);
+    let timezone_handler = use_state(browser_timezone);
+    let quiet_hours_start_handler = use_state(|| None::<String>);
+    let quiet_hours_end_handler = use_state(|| None::<String>);
+    let alert_cooldown_minutes_handler = use_state(|| 120);
+    let notify_all_clear_handler = use_state(|| false);
+    let locations_handler = use_state(HashMap::<String, UserRegisterLocation>::new);
 
     let registration_info = UserRegisterBody {
         email: email_handler.deref().clone(),
-        alert_threshold: alert_threshold_handler.deref().clone(),
+        alert_criterion: alert_criterion_handler.deref().clone(),
+        min_visibility_score: *min_visibility_score_handler,
+        max_cloud_cover: *max_cloud_cover_handler,
+        timezone: timezone_handler.deref().clone(),
+        quiet_hours_start: quiet_hours_start_handler.deref().clone(),
+        quiet_hours_end: quiet_hours_end_handler.deref().clone(),
+        alert_cooldown_minutes: *alert_cooldown_minutes_handler,
+        notify_all_clear: *notify_all_clear_handler,
         locations: locations_handler
             .deref()
             .values()
@@ -32,6 +47,18 @@ pub fn registration_form() -> Html {
         use_async(async move { register(registration_info).await })
     };
 
+    let verification_resend = {
+        let email = registration_info.email.clone();
+        use_async(async move { resend_verification(email).await })
+    };
+
+    let onclick_resend = {
+        let verification_resend = verification_resend.clone();
+        Callback::from(move |_: MouseEvent| {
+            verification_resend.run();
+        })
+    };
+
     let onsubmit = {
         let user_register = user_register.clone();
         Callback::from(move |e: FocusEvent| {
@@ -58,6 +85,20 @@ pub fn registration_form() -> Html {
                                         <p class="mb-0">{"You'll shortly receive an email to verify your account so that you can start to receive aurora alerts. In the meantime, head back to the "}
                                         <LinkHome text="homepage" />
                                         {" to see the latest aurora activity."}</p>
+                                        <p class="mt-2 mb-0">
+                                            {
+                                                if verification_resend.data.is_some() {
+                                                    html! { {"We've sent you another verification email."} }
+                                                } else {
+                                                    html! {
+                                                        <>
+                                                            {"Didn't receive it? "}
+                                                            <button onclick={onclick_resend} disabled={verification_resend.loading} type="button" class={classes!("btn", "btn-link", "p-0", "align-baseline")}>{"Resend the verification email"}</button>
+                                                        </>
+                                                    }
+                                                }
+                                            }
+                                        </p>
                                     </div>
                                 </div>
                             </div>
@@ -91,8 +132,20 @@ pub fn registration_form() -> Html {
                                 <div class="col-lg-auto">
                                     <Form {onsubmit}>
                                         <EmailField handler={email_handler} />
-                                        <AlertThresholdField handler={alert_threshold_handler} />
-                                        <LocationsField handler={locations_handler} />
+                                        <AlertCriterionField handler={alert_criterion_handler.clone()} />
+                                        {
+                                            if *alert_criterion_handler == "visibility_score" {
+                                                html! { <MinVisibilityScoreField handler={min_visibility_score_handler} /> }
+                                            } else {
+                                                html! {}
+                                            }
+                                        }
+                                        <MaxCloudCoverField handler={max_cloud_cover_handler} />
+                                        <TimezoneField handler={timezone_handler} />
+                                        <QuietHoursField start_handler={quiet_hours_start_handler} end_handler={quiet_hours_end_handler} />
+                                        <AlertCooldownField handler={alert_cooldown_minutes_handler} />
+                                        <NotifyAllClearField handler={notify_all_clear_handler} />
+                                        <LocationsField handler={locations_handler} show_thresholds={*alert_criterion_handler == "alert_level"} />
                                         <button disabled={!valid_form} type="submit" class={classes!("btn", "btn-primary", "mb-3")}>{"Register"}</button>
                                     </Form>
                                 </div>
@@ -106,12 +159,12 @@ pub fn registration_form() -> Html {
 }
 
 #[derive(Properties, PartialEq)]
-struct EmailFieldProps {
-    handler: UseStateHandle<String>,
+pub(super) struct EmailFieldProps {
+    pub(super) handler: UseStateHandle<String>,
 }
 
 #[function_component(EmailField)]
-fn email_field(props: &EmailFieldProps) -> Html {
+pub(super) fn email_field(props: &EmailFieldProps) -> Html {
     let oninput = {
         let handler = props.handler.clone();
         Callback::from(move |e: InputEvent| {
@@ -129,12 +182,12 @@ fn email_field(props: &EmailFieldProps) -> Html {
 }
 
 #[derive(Properties, PartialEq)]
-struct AlertThresholdFieldProps {
-    handler: UseStateHandle<String>,
+pub(super) struct AlertCriterionFieldProps {
+    pub(super) handler: UseStateHandle<String>,
 }
 
-#[function_component(AlertThresholdField)]
-fn alert_threshold_field(props: &AlertThresholdFieldProps) -> Html {
+#[function_component(AlertCriterionField)]
+pub(super) fn alert_criterion_field(props: &AlertCriterionFieldProps) -> Html {
     let oninput = {
         let handler = props.handler.clone();
         Callback::from(move |e: InputEvent| {
@@ -145,23 +198,210 @@ fn alert_threshold_field(props: &AlertThresholdFieldProps) -> Html {
 
     html! {
         <div class={classes!("form-floating", "mb-3")}>
-        <select {oninput} id="user-alert-threshold" class={classes!("form-select")}>
-            <option value="yellow" selected=true>{"Yellow"}</option>
-            <option value="amber">{"Amber"}</option>
-            <option value="red">{"Red"}</option>
+        <select {oninput} id="user-alert-criterion" class={classes!("form-select")}>
+            <option value="alert_level" selected={*props.handler == "alert_level"}>{"Alert level"}</option>
+            <option value="visibility_score" selected={*props.handler == "visibility_score"}>{"Visibility score"}</option>
         </select>
-        <label for="user-alert-threshold" class={classes!("form-label")}>{"Alert threshold"}</label>
+        <label for="user-alert-criterion" class={classes!("form-label")}>{"Alert me based on"}</label>
         </div>
     }
 }
 
 #[derive(Properties, PartialEq)]
-struct LocationsFieldProps {
-    handler: UseStateHandle<HashMap<String, i64>>,
+pub(super) struct MinVisibilityScoreFieldProps {
+    pub(super) handler: UseStateHandle<i16>,
+}
+
+#[function_component(MinVisibilityScoreField)]
+pub(super) fn min_visibility_score_field(props: &MinVisibilityScoreFieldProps) -> Html {
+    let oninput = {
+        let handler = props.handler.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(score) = el.value().parse::<i16>() {
+                handler.set(score.clamp(0, 100));
+            }
+        })
+    };
+
+    html! {
+        <>
+            <div class={classes!("form-floating")}>
+                <input {oninput} id="user-min-visibility-score" type="number" min="0" max="100" value={props.handler.to_string()} class={classes!("form-control")} />
+                <label for="user-min-visibility-score" class={classes!("form-label")}>{"Minimum visibility score"}</label>
+            </div>
+            <div class="form-text mb-3 ms-1">
+                {"Scored out of 100, from the alert level, cloud cover, darkness and moonlight at your locations"}
+            </div>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub(super) struct MaxCloudCoverFieldProps {
+    pub(super) handler: UseStateHandle<i16>,
+}
+
+#[function_component(MaxCloudCoverField)]
+pub(super) fn max_cloud_cover_field(props: &MaxCloudCoverFieldProps) -> Html {
+    let oninput = {
+        let handler = props.handler.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(cloud_cover) = el.value().parse::<i16>() {
+                handler.set(cloud_cover.clamp(0, 100));
+            }
+        })
+    };
+
+    html! {
+        <>
+            <div class={classes!("form-floating")}>
+                <input {oninput} id="user-max-cloud-cover" type="number" min="0" max="100" value={props.handler.to_string()} class={classes!("form-control")} />
+                <label for="user-max-cloud-cover" class={classes!("form-label")}>{"Maximum cloud cover (%)"}</label>
+            </div>
+            <div class="form-text mb-3 ms-1">
+                {"Only alert me when at least one of my locations is this clear"}
+            </div>
+        </>
+    }
+}
+
+/// The IANA name of the browser's timezone, e.g. "Europe/London".
+fn browser_timezone() -> String {
+    let options = js_sys::Intl::DateTimeFormat::new(&js_sys::Array::new(), &js_sys::Object::new())
+        .resolved_options();
+    js_sys::Reflect::get(&options, &JsValue::from_str("timeZone"))
+        .ok()
+        .and_then(|timezone| timezone.as_string())
+        .unwrap_or_else(|| "Europe/London".to_string())
+}
+
+#[derive(Properties, PartialEq)]
+pub(super) struct TimezoneFieldProps {
+    pub(super) handler: UseStateHandle<String>,
+}
+
+#[function_component(TimezoneField)]
+pub(super) fn timezone_field(props: &TimezoneFieldProps) -> Html {
+    let oninput = {
+        let handler = props.handler.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            handler.set(el.value());
+        })
+    };
+
+    html! {
+        <div class={classes!("form-floating", "mb-3")}>
+            <input {oninput} id="user-timezone" type="text" value={props.handler.deref().clone()} class={classes!("form-control")} />
+            <label for="user-timezone" class={classes!("form-label")}>{"Timezone"}</label>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub(super) struct QuietHoursFieldProps {
+    pub(super) start_handler: UseStateHandle<Option<String>>,
+    pub(super) end_handler: UseStateHandle<Option<String>>,
+}
+
+#[function_component(QuietHoursField)]
+pub(super) fn quiet_hours_field(props: &QuietHoursFieldProps) -> Html {
+    // time inputs give "HH:MM", whereas the server expects seconds too
+    let to_time = |value: String| (!value.is_empty()).then(|| format!("{value}:00"));
+
+    let oninput_start = {
+        let handler = props.start_handler.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            handler.set(to_time(el.value()));
+        })
+    };
+
+    let oninput_end = {
+        let handler = props.end_handler.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            handler.set(to_time(el.value()));
+        })
+    };
+
+    html! {
+        <>
+            <div class={classes!("input-group")}>
+                <div class="form-floating">
+                    <input oninput={oninput_start} id="user-quiet-hours-start" type="time" value={props.start_handler.deref().clone().unwrap_or_default()} class={classes!("form-control")} />
+                    <label for="user-quiet-hours-start" class={classes!("form-label")}>{"Quiet from"}</label>
+                </div>
+                <div class="form-floating">
+                    <input oninput={oninput_end} id="user-quiet-hours-end" type="time" value={props.end_handler.deref().clone().unwrap_or_default()} class={classes!("form-control")} />
+                    <label for="user-quiet-hours-end" class={classes!("form-label")}>{"Quiet until"}</label>
+                </div>
+            </div>
+            <div class="form-text mb-3 ms-1">
+                {"Optionally, times in your timezone between which you won't be emailed"}
+            </div>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub(super) struct AlertCooldownFieldProps {
+    pub(super) handler: UseStateHandle<i32>,
+}
+
+#[function_component(AlertCooldownField)]
+pub(super) fn alert_cooldown_field(props: &AlertCooldownFieldProps) -> Html {
+    let oninput = {
+        let handler = props.handler.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            if let Ok(minutes) = el.value().parse::<i32>() {
+                handler.set(minutes.max(0));
+            }
+        })
+    };
+
+    html! {
+        <div class={classes!("form-floating", "mb-3")}>
+            <input {oninput} id="user-alert-cooldown" type="number" min="0" step="15" value={props.handler.to_string()} class={classes!("form-control")} />
+            <label for="user-alert-cooldown" class={classes!("form-label")}>{"Minimum minutes between alerts"}</label>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub(super) struct NotifyAllClearFieldProps {
+    pub(super) handler: UseStateHandle<bool>,
+}
+
+#[function_component(NotifyAllClearField)]
+pub(super) fn notify_all_clear_field(props: &NotifyAllClearFieldProps) -> Html {
+    let onchange = {
+        let handler = props.handler.clone();
+        Callback::from(move |e: Event| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            handler.set(el.checked());
+        })
+    };
+
+    html! {
+        <div class={classes!("form-check", "mb-3", "ms-1")}>
+            <input {onchange} id="user-notify-all-clear" type="checkbox" checked={*props.handler} class={classes!("form-check-input")} />
+            <label for="user-notify-all-clear" class={classes!("form-check-label")}>{"Email me when activity has subsided"}</label>
+        </div>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+pub(super) struct LocationsFieldProps {
+    pub(super) handler: UseStateHandle<HashMap<String, UserRegisterLocation>>,
+    pub(super) show_thresholds: bool,
 }
 
 #[function_component(LocationsField)]
-fn locations_field(props: &LocationsFieldProps) -> Html {
+pub(super) fn locations_field(props: &LocationsFieldProps) -> Html {
     let chosen_locations = props.handler.clone();
 
     let location = use_state_eq(String::new);
@@ -200,7 +440,9 @@ fn locations_field(props: &LocationsFieldProps) -> Html {
                 .unwrap();
 
             let mut new_chosen_locations = chosen_locations.deref().clone();
-            new_chosen_locations.insert(name.to_string(), *location_id);
+            new_chosen_locations
+                .entry(name.to_string())
+                .or_insert_with(|| UserRegisterLocation::new(*location_id));
             chosen_locations.set(new_chosen_locations);
 
             // clear the input box, re-disable the Select button and clear the datalist
@@ -246,7 +488,7 @@ fn locations_field(props: &LocationsFieldProps) -> Html {
                     {"this map"}
                 </a>
             </div>
-            <ChosenLocations locations={chosen_locations} />
+            <ChosenLocations locations={chosen_locations} show_thresholds={props.show_thresholds} />
         </>
     }
 }
@@ -254,6 +496,9 @@ fn locations_field(props: &LocationsFieldProps) -> Html {
 #[derive(Properties, PartialEq)]
 struct ChosenLocationProps {
     name: String,
+    location: UserRegisterLocation,
+    show_threshold: bool,
+    location_to_update: UseStateHandle<Option<(String, UserRegisterLocation)>>,
     location_to_remove: UseStateHandle<Option<String>>,
 }
 
@@ -268,26 +513,87 @@ fn chosen_location(props: &ChosenLocationProps) -> Html {
         })
     };
 
+    let oninput_threshold = {
+        let name = props.name.clone();
+        let location = props.location.clone();
+        let handle = props.location_to_update.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            let mut location = location.clone();
+            location.alert_threshold = el.value();
+            handle.set(Some((name.clone(), location)));
+        })
+    };
+
+    let oninput_cloud_cover = {
+        let name = props.name.clone();
+        let location = props.location.clone();
+        let handle = props.location_to_update.clone();
+        Callback::from(move |e: InputEvent| {
+            let el: HtmlInputElement = e.target_unchecked_into();
+            let mut location = location.clone();
+            // leaving the field empty falls back to the maximum cloud cover for all locations
+            location.max_cloud_cover = el
+                .value()
+                .parse::<i16>()
+                .ok()
+                .map(|cloud_cover| cloud_cover.clamp(0, 100));
+            handle.set(Some((name.clone(), location)));
+        })
+    };
+
+    let threshold = props.location.alert_threshold.as_str();
+    let cloud_cover = props
+        .location
+        .max_cloud_cover
+        .map(|cloud_cover| cloud_cover.to_string())
+        .unwrap_or_default();
+
     html! {
         <li class={classes!("list-group-item", "d-flex", "justify-content-between", "align-items-center")}>
-            <span>{name}</span>
-            <svg {onclick} xmlns="http://www.w3.org/2000/svg" role="button" class={classes!("bi", "bi-x-circle", "icon-button")} viewBox="0 0 16 16" style="">
-                <path  d="M8 15A7 7 0 1 1 8 1a7 7 0 0 1 0 14zm0 1A8 8 0 1 0 8 0a8 8 0 0 0 0 16z"/>
-                <path  d="M4.646 4.646a.5.5 0 0 1 .708 0L8 7.293l2.646-2.647a.5.5 0 0 1 .708.708L8.707 8l2.647 2.646a.5.5 0 0 1-.708.708L8 8.707l-2.646 2.647a.5.5 0 0 1-.708-.708L7.293 8 4.646 5.354a.5.5 0 0 1 0-.708z"/>
-            </svg>
+            <span class="me-3">{name}</span>
+            <div class="d-flex align-items-center">
+                {
+                    if props.show_threshold {
+                        html! {
+                            <select oninput={oninput_threshold} aria-label="Alert threshold" class={classes!("form-select", "form-select-sm", "me-2")}>
+                                <option value="yellow" selected={threshold == "yellow"}>{"Yellow"}</option>
+                                <option value="amber" selected={threshold == "amber"}>{"Amber"}</option>
+                                <option value="red" selected={threshold == "red"}>{"Red"}</option>
+                            </select>
+                        }
+                    } else {
+                        html! {}
+                    }
+                }
+                <input oninput={oninput_cloud_cover} type="number" min="0" max="100" value={cloud_cover} placeholder="Max cloud %" aria-label="Maximum cloud cover (%)" class={classes!("form-control", "form-control-sm", "me-2")} style="width: 8rem;" />
+                <svg {onclick} xmlns="http://www.w3.org/2000/svg" role="button" class={classes!("bi", "bi-x-circle", "icon-button")} viewBox="0 0 16 16" style="">
+                    <path  d="M8 15A7 7 0 1 1 8 1a7 7 0 0 1 0 14zm0 1A8 8 0 1 0 8 0a8 8 0 0 0 0 16z"/>
+                    <path  d="M4.646 4.646a.5.5 0 0 1 .708 0L8 7.293l2.646-2.647a.5.5 0 0 1 .708.708L8.707 8l2.647 2.646a.5.5 0 0 1-.708.708L8 8.707l-2.646 2.647a.5.5 0 0 1-.708-.708L7.293 8 4.646 5.354a.5.5 0 0 1 0-.708z"/>
+                </svg>
+            </div>
         </li>
     }
 }
 
 #[derive(Properties, PartialEq)]
 struct ChosenLocationsProps {
-    locations: UseStateHandle<HashMap<String, i64>>,
+    locations: UseStateHandle<HashMap<String, UserRegisterLocation>>,
+    show_thresholds: bool,
 }
 
 #[function_component(ChosenLocations)]
 fn chosen_locations(props: &ChosenLocationsProps) -> Html {
+    let location_to_update = use_state(|| None::<(String, UserRegisterLocation)>);
     let location_to_remove = use_state(|| None::<String>);
 
+    if let Some((name, location)) = location_to_update.deref().clone() {
+        let mut new_locations = props.locations.deref().clone();
+        new_locations.insert(name, location);
+        props.locations.set(new_locations);
+        location_to_update.set(None);
+    }
+
     if let Some(location) = location_to_remove.deref().clone() {
         let mut new_locations = props.locations.deref().clone();
         new_locations.remove(&location);
@@ -295,16 +601,22 @@ fn chosen_locations(props: &ChosenLocationsProps) -> Html {
         location_to_remove.set(None);
     }
 
-    let mut locations = props.locations.keys().collect::<Vec<_>>();
-    locations.sort_unstable();
+    let mut locations = props.locations.iter().collect::<Vec<_>>();
+    locations.sort_unstable_by_key(|(name, _)| *name);
     let hide = props.locations.len() < 1;
     html! {
         <div class={classes!(if hide {"d-none"} else {""})}>
             <p class="mb-0 ms-1">{format!("{}/5 locations chosen", props.locations.len())}</p>
             <ul class={classes!("list-group", "mb-3", "user-select-none")}>
             {
-                locations.iter().map(|&name| html! {
-                    <ChosenLocation name={name.clone()} location_to_remove={location_to_remove.clone()} />
+                locations.iter().map(|&(name, location)| html! {
+                    <ChosenLocation
+                        name={name.clone()}
+                        location={location.clone()}
+                        show_threshold={props.show_thresholds}
+                        location_to_update={location_to_update.clone()}
+                        location_to_remove={location_to_remove.clone()}
+                    />
                 }).collect::<Html>()
             }
             </ul>
@@ -0,0 +1,42 @@
+use std::ops::Deref;
+
+use yew::prelude::*;
+use yew_hooks::use_async;
+
+use super::registration::EmailField;
+use super::{Form, FormStatus};
+use crate::services::user::resend_verification;
+
+#[function_component(ResendVerificationForm)]
+pub fn resend_verification_form() -> Html {
+    let email_handler = use_state(String::new);
+
+    let resend = {
+        let email = email_handler.deref().clone();
+        use_async(async move { resend_verification(email).await })
+    };
+
+    let onsubmit = {
+        let resend = resend.clone();
+        Callback::from(move |e: FocusEvent| {
+            e.prevent_default();
+            resend.run();
+        })
+    };
+
+    let valid_form = !resend.loading && !email_handler.is_empty();
+
+    html! {
+        <>
+            <FormStatus
+                succeeded={resend.data.is_some()}
+                failed={resend.error.is_some()}
+                message={"If that email address is registered but not yet verified, you'll shortly receive a new verification email."}
+            />
+            <Form {onsubmit}>
+                <EmailField handler={email_handler} />
+                <button disabled={!valid_form} type="submit" class={classes!("btn", "btn-primary", "mb-3")}>{"Resend verification email"}</button>
+            </Form>
+        </>
+    }
+}
@@ -1,6 +1,7 @@
 mod about;
 mod home;
 mod internal_server_error;
+mod manage;
 mod page_not_found;
 mod register;
 mod unsubscribe;
@@ -9,6 +10,7 @@ mod verify;
 pub use about::About;
 pub use home::Home;
 pub use internal_server_error::InternalServerError;
+pub use manage::Manage;
 pub use page_not_found::PageNotFound;
 pub use register::Register;
 pub use unsubscribe::Unsubscribe;
@@ -1,6 +1,7 @@
 use yew::prelude::*;
 
 use crate::components::chart::ActivityChart;
+use crate::components::forecast::CloudCoverForecast;
 use crate::routes::LinkRegister;
 
 #[function_component(Home)]
@@ -24,6 +25,13 @@ pub fn home() -> Html {
                     </p>
                 </div>
             </div>
+            <div class="row justify-content-center">
+                <div class="col-8">
+                    <h5>{"Cloud cover forecast"}</h5>
+                    <p>{"Whether an aurora is visible depends on clear skies. Search for a location to see its forecast cloud cover over the next day."}</p>
+                    <CloudCoverForecast />
+                </div>
+            </div>
             <br />
             <div class="row justify-content-center">
                 <div class="col-10">
@@ -0,0 +1,76 @@
+use yew::prelude::*;
+
+use crate::components::forms::{ManageForm, ManageLinkForm};
+use crate::error::Error;
+use crate::hooks::use_query_params;
+use crate::routes::RedirectInternalServerError;
+use crate::services::user::get_settings;
+use crate::types::user::TokenParams;
+
+#[function_component(Manage)]
+pub fn manage() -> Html {
+    // without a token, the user needs to be emailed a link with one first
+    let token = use_query_params::<TokenParams>()
+        .ok()
+        .map(|params| params.token);
+
+    html! {
+        <>
+            <div class="row mb-5 justify-content-center">
+                <div class="col-lg-10">
+                    <p>{"Change the locations you're subscribed to, the thresholds you're alerted at, and your other preferences. To keep your subscription secure, you'll need to follow a link which we'll email to you."}</p>
+                </div>
+            </div>
+            <div class={classes!("row", "justify-content-center")}>
+                <div class="col-lg-6">
+                    {
+                        if let Some(token) = token {
+                            html! { <ManageSettings {token} /> }
+                        } else {
+                            html! { <ManageLinkForm /> }
+                        }
+                    }
+                </div>
+            </div>
+        </>
+    }
+}
+
+#[derive(Properties, PartialEq)]
+struct ManageSettingsProps {
+    token: String,
+}
+
+#[function_component(ManageSettings)]
+fn manage_settings(props: &ManageSettingsProps) -> Html {
+    let state = {
+        let token = props.token.clone();
+        yew_hooks::use_async_with_options(
+            async move { get_settings(token).await },
+            yew_hooks::UseAsyncOptions::enable_auto(),
+        )
+    };
+
+    if let Some(Error::Unauthorized) = &state.error {
+        return html! {
+            <>
+                <p>{"This link is invalid or has expired. Enter your email address to be sent a new one."}</p>
+                <ManageLinkForm />
+            </>
+        };
+    }
+    if let Some(e) = &state.error {
+        log::warn!("Error within manage callback: {e}");
+        return html! { <RedirectInternalServerError /> };
+    }
+
+    if let Some(settings) = &state.data {
+        html! {
+            <ManageForm token={props.token.clone()} settings={settings.clone()} />
+        }
+    } else {
+        html! {
+            <div></div>
+        }
+    }
+}
@@ -1,13 +1,14 @@
 use yew::prelude::*;
 
+use crate::error::Error;
 use crate::hooks::use_query_params;
 use crate::routes::{LinkHome, RedirectInternalServerError, RedirectNotFound};
 use crate::services::user::unsubscribe;
-use crate::types::user::UnsubscribeParams;
+use crate::types::user::TokenParams;
 
 #[function_component(Unsubscribe)]
 pub fn unsubscribe() -> Html {
-    let params: UnsubscribeParams = match use_query_params() {
+    let params: TokenParams = match use_query_params() {
         Ok(params) => params,
         Err(e) => {
             log::warn!("Required parameters not provided: {e}");
@@ -17,13 +18,21 @@ pub fn unsubscribe() -> Html {
 
     let state = {
         yew_hooks::use_async_with_options(
-            async move { unsubscribe(params.user_id, params.email).await },
+            async move { unsubscribe(params.token).await },
             yew_hooks::UseAsyncOptions::enable_auto(),
         )
     };
 
     {
         let state = state.clone();
+        if let Some(Error::Unauthorized) = &state.error {
+            return html! {
+                <div>
+                    <p>{"This unsubscribe link is invalid or has expired. Please use the link in your most recent aurora alert instead."}</p>
+                    <LinkHome text={"Return to the homepage"} />
+                </div>
+            };
+        }
         if let Some(e) = &state.error {
             log::warn!("Error within unsubscribe callback: {e}");
             return html! { <RedirectInternalServerError /> };
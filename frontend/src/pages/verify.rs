@@ -1,13 +1,15 @@
 use yew::prelude::*;
 
+use crate::components::forms::ResendVerificationForm;
+use crate::error::Error;
 use crate::hooks::use_query_params;
 use crate::routes::{LinkHome, RedirectInternalServerError, RedirectNotFound};
 use crate::services::user::verify;
-use crate::types::user::VerifyUserParams;
+use crate::types::user::TokenParams;
 
 #[function_component(Verify)]
 pub fn verify() -> Html {
-    let params: VerifyUserParams = match use_query_params() {
+    let params: TokenParams = match use_query_params() {
         Ok(params) => params,
         Err(e) => {
             log::warn!("Required parameters not provided: {e}");
@@ -17,13 +19,23 @@ pub fn verify() -> Html {
 
     let state = {
         yew_hooks::use_async_with_options(
-            async move { verify(params.user_id, params.email).await },
+            async move { verify(params.token).await },
             yew_hooks::UseAsyncOptions::enable_auto(),
         )
     };
 
     {
         let state = state.clone();
+        if let Some(Error::Unauthorized) = &state.error {
+            return html! {
+                <div class={classes!("row", "justify-content-center")}>
+                    <div class="col-lg-6">
+                        <p>{"This verification link is invalid or has expired. Enter your email address to be sent a new one."}</p>
+                        <ResendVerificationForm />
+                    </div>
+                </div>
+            };
+        }
         if let Some(e) = &state.error {
             log::warn!("Error within verify callback: {e}");
             return html! { <RedirectInternalServerError /> };
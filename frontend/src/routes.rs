@@ -1,7 +1,9 @@
 use yew::prelude::*;
 use yew_router::prelude::*;
 
-use crate::pages::{About, Home, InternalServerError, PageNotFound, Register, Unsubscribe, Verify};
+use crate::pages::{
+    About, Home, InternalServerError, Manage, PageNotFound, Register, Unsubscribe, Verify,
+};
 
 #[derive(Clone, PartialEq, Routable, Debug)]
 pub enum Route {
@@ -15,6 +17,8 @@ pub enum Route {
     Verify,
     #[at("/unsubscribe")]
     Unsubscribe,
+    #[at("/manage")]
+    Manage,
     #[at("/internal-server-error")]
     InternalServerError,
     #[not_found]
@@ -31,6 +35,7 @@ fn switch(routes: &Route) -> Html {
         Route::Unsubscribe => {
             html! { <Unsubscribe  /> }
         }
+        Route::Manage => html! { <Manage /> },
         Route::InternalServerError => html! { <InternalServerError /> },
         Route::NotFound => html! { <PageNotFound /> },
     }
@@ -87,6 +92,21 @@ pub fn link_register(props: &LinkRegisterProps) -> Html {
     }
 }
 
+#[derive(Properties, PartialEq)]
+pub struct LinkManageProps {
+    pub text: String,
+    #[prop_or_default]
+    pub classes: Classes,
+}
+
+#[function_component(LinkManage)]
+pub fn link_manage(props: &LinkManageProps) -> Html {
+    let LinkManageProps { text, classes } = props;
+    html! {
+        <Link<Route> to={Route::Manage}  classes={classes.clone()}>{text}</Link<Route>>
+    }
+}
+
 #[derive(Properties, PartialEq)]
 pub struct LinkAboutProps {
     pub text: String,
@@ -1,4 +1,6 @@
 pub mod charts;
+pub mod forecasts;
 pub mod locations;
 pub mod requests;
+pub mod stations;
 pub mod user;
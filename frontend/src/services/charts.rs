@@ -1,15 +1,15 @@
 use plotly::{
-    common::{Marker, Title},
+    common::{DashType, Marker, Title},
     configuration::DisplayModeBar,
     layout::{
         themes::{PLOTLY_DARK, PLOTLY_WHITE},
-        Axis,
+        Axis, Shape, ShapeLine, ShapeType,
     },
 };
 use serde::Deserialize;
 
 use super::requests;
-use crate::common::{ActivityData, ActivityDataPoint};
+use crate::common::{ActivityData, ActivityDataPoint, Aggregation, AlertLevel, Thresholds};
 use crate::error::Error;
 use crate::theme::{ThemeMode, AMBER, GREEN, RED, YELLOW};
 
@@ -17,8 +17,11 @@ type DateTimeUtc = chrono::DateTime<chrono::Utc>;
 
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct ActivityDataResponse {
+    pub station: String,
     pub updated_at: DateTimeUtc,
     pub activities: Vec<ActivityDataPoint>,
+    #[serde(default)]
+    pub thresholds: Option<Thresholds>,
 }
 
 pub trait ActivityDataExt {
@@ -29,9 +32,10 @@ pub trait ActivityDataExt {
 impl ActivityDataExt for ActivityData {
     fn from_response(resp: ActivityDataResponse) -> Self {
         ActivityData {
+            station: resp.station,
             updated_at: resp.updated_at,
-            // This unwrap won't fail because we already ensured that 24 elements are present on the server side.
-            activities: resp.activities.try_into().unwrap(),
+            activities: resp.activities,
+            thresholds: resp.thresholds,
         }
     }
 
@@ -49,16 +53,11 @@ impl ActivityDataExt for ActivityData {
             Marker::new().color_array(
                 self.activities
                     .iter()
-                    .map(|a| {
-                        if a.value < 50. {
-                            GREEN
-                        } else if a.value < 100. {
-                            YELLOW
-                        } else if a.value < 200. {
-                            AMBER
-                        } else {
-                            RED
-                        }
+                    .map(|a| match a.alert_level {
+                        AlertLevel::Green => GREEN,
+                        AlertLevel::Yellow => YELLOW,
+                        AlertLevel::Amber => AMBER,
+                        AlertLevel::Red => RED,
                     })
                     .collect(),
             ),
@@ -66,14 +65,15 @@ impl ActivityDataExt for ActivityData {
         .hover_template(r#"<b>%{x|%-d %b %y %H:%M}</b><br>Activity: %{y:.1f}nT<extra></extra>"#);
         plot.add_trace(trace);
 
-        let layout = plotly::Layout::new()
+        let mut layout = plotly::Layout::new()
             .template(match theme_mode {
                 ThemeMode::Dark => &*PLOTLY_DARK,
                 ThemeMode::Light => &*PLOTLY_WHITE,
             })
             .title(
                 Title::new(&format!(
-                    "<b>Lastest Geomagnetic Activity</b><br><sub>Last updated {}</sub>",
+                    "<b>Lastest Geomagnetic Activity</b><br><sub>Station {} - last updated {}</sub>",
+                    self.station,
                     self.updated_at
                         .with_timezone(&chrono::Local)
                         .format("%-d %b %y %H:%M %Z")
@@ -94,6 +94,27 @@ impl ActivityDataExt for ActivityData {
             )
             .y_axis(Axis::new().title("Activity (nT)".into()).fixed_range(true));
 
+        // Mark the point at which each alert level begins, as reported by the station.
+        if let Some(thresholds) = &self.thresholds {
+            for (threshold, colour) in [
+                (thresholds.yellow, YELLOW),
+                (thresholds.amber, AMBER),
+                (thresholds.red, RED),
+            ] {
+                layout.add_shape(
+                    Shape::new()
+                        .shape_type(ShapeType::Line)
+                        .x_ref("paper")
+                        .x0(0)
+                        .x1(1)
+                        .y_ref("y")
+                        .y0(f64::from(threshold))
+                        .y1(f64::from(threshold))
+                        .line(ShapeLine::new().color(colour).dash(DashType::Dash)),
+                );
+            }
+        }
+
         plot.set_layout(layout);
 
         let config = plotly::Configuration::new()
@@ -106,10 +127,63 @@ impl ActivityDataExt for ActivityData {
     }
 }
 
-pub async fn get_activity_data(end: DateTimeUtc) -> Result<ActivityData, Error> {
-    let params = format!("?end={end:?}");
+/// The span of time shown on the activity chart.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ChartRange {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl ChartRange {
+    pub const ALL: [ChartRange; 4] = [Self::Day, Self::Week, Self::Month, Self::Year];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Day => "Day",
+            Self::Week => "Week",
+            Self::Month => "Month",
+            Self::Year => "Year",
+        }
+    }
+
+    /// Longer ranges are aggregated, to keep the number of bars readable.
+    pub fn aggregation(&self) -> Aggregation {
+        match self {
+            Self::Day | Self::Week => Aggregation::Hourly,
+            Self::Month => Aggregation::DailyMax,
+            Self::Year => Aggregation::WeeklyMax,
+        }
+    }
+
+    /// The start of the range which finishes at `end`, such that `end` falls
+    /// into the final bucket.
+    pub fn start(&self, end: DateTimeUtc) -> DateTimeUtc {
+        let span = match self {
+            Self::Day => chrono::Duration::days(1),
+            Self::Week => chrono::Duration::weeks(1),
+            Self::Month => chrono::Duration::days(30),
+            Self::Year => chrono::Duration::days(365),
+        };
+        end - span + self.aggregation().bucket_duration()
+    }
+}
+
+pub async fn get_activity_data(
+    end: DateTimeUtc,
+    range: ChartRange,
+    station: String,
+) -> Result<ActivityData, Error> {
+    let params = serde_urlencoded::to_string([
+        ("start", range.start(end).to_rfc3339()),
+        ("end", end.to_rfc3339()),
+        ("aggregation", range.aggregation().to_string()),
+        ("station", station),
+    ])
+    .map_err(|_| Error::RequestError)?;
 
-    let data = requests::get::<ActivityDataResponse>(format!("api/activity{params}")).await?;
+    let data = requests::get::<ActivityDataResponse>(format!("api/activity?{params}")).await?;
 
     Ok(ActivityData::from_response(data))
 }
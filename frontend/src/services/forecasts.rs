@@ -0,0 +1,11 @@
+use crate::error::Error;
+use crate::requests;
+use crate::types::forecasts::{ForecastBody, ForecastPoint};
+
+pub async fn get_forecast(location_id: i64) -> Result<Vec<ForecastPoint>, Error> {
+    Ok(
+        requests::get::<ForecastBody>(format!("/api/forecast?location_id={location_id}"))
+            .await?
+            .forecast,
+    )
+}
@@ -11,7 +11,7 @@ where
     B: Serialize + std::fmt::Debug,
 {
     let allow_body = match method {
-        Method::POST | Method::PUT => true,
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE => true,
         _ => false,
     };
 
@@ -62,9 +62,18 @@ where
     request(Method::POST, url, body).await
 }
 
-pub async fn delete<T>(url: String) -> Result<T, Error>
+pub async fn patch<T, B>(url: String, body: B) -> Result<T, Error>
 where
     T: DeserializeOwned + Debug + 'static,
+    B: Serialize + Debug,
+{
+    request(Method::PATCH, url, body).await
+}
+
+pub async fn delete<T, B>(url: String, body: B) -> Result<T, Error>
+where
+    T: DeserializeOwned + Debug + 'static,
+    B: Serialize + Debug,
 {
-    request(Method::DELETE, url, ()).await
+    request(Method::DELETE, url, body).await
 }
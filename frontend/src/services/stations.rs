@@ -0,0 +1,12 @@
+use crate::error::Error;
+use crate::requests;
+use crate::types::stations::StationsBody;
+
+pub async fn get_stations() -> Result<Vec<String>, Error> {
+    Ok(requests::get::<StationsBody>("/api/stations".to_string())
+        .await?
+        .stations
+        .into_iter()
+        .map(|station| station.station)
+        .collect())
+}
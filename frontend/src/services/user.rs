@@ -2,16 +2,40 @@ use common::ApiResponse;
 
 use crate::error::Error;
 use crate::requests;
-use crate::types::user::UserRegisterBody;
+use crate::types::user::{
+    EmailBody, TokenParams, UserLocationsBody, UserPreferencesBody, UserRegisterBody, UserSettings,
+};
 
 pub async fn register(user_info: UserRegisterBody) -> Result<ApiResponse, Error> {
     requests::post::<ApiResponse, _>("/api/users".to_string(), user_info).await
 }
 
-pub async fn verify(user_id: String, email: String) -> Result<ApiResponse, Error> {
-    requests::get::<ApiResponse>(format!("/api/verify?user_id={}&email={}", user_id, email)).await
+pub async fn verify(token: String) -> Result<ApiResponse, Error> {
+    requests::patch::<ApiResponse, _>("/api/users/verify".to_string(), TokenParams { token }).await
 }
 
-pub async fn unsubscribe(user_id: String, email: String) -> Result<ApiResponse, Error> {
-    requests::delete::<ApiResponse>(format!("/api/users?user_id={}&email={}", user_id, email)).await
+pub async fn unsubscribe(token: String) -> Result<ApiResponse, Error> {
+    requests::delete::<ApiResponse, _>("/api/users".to_string(), TokenParams { token }).await
+}
+
+pub async fn resend_verification(email: String) -> Result<ApiResponse, Error> {
+    requests::post::<ApiResponse, _>("/api/users/verify/resend".to_string(), EmailBody { email })
+        .await
+}
+
+pub async fn request_manage_link(email: String) -> Result<ApiResponse, Error> {
+    requests::post::<ApiResponse, _>("/api/users/manage".to_string(), EmailBody { email }).await
+}
+
+pub async fn get_settings(token: String) -> Result<UserSettings, Error> {
+    // tokens are URL safe, so don't need encoding
+    requests::get::<UserSettings>(format!("/api/users/manage?token={token}")).await
+}
+
+pub async fn update_preferences(preferences: UserPreferencesBody) -> Result<ApiResponse, Error> {
+    requests::patch::<ApiResponse, _>("/api/users/preferences".to_string(), preferences).await
+}
+
+pub async fn update_locations(locations: UserLocationsBody) -> Result<ApiResponse, Error> {
+    requests::patch::<ApiResponse, _>("/api/users/locations".to_string(), locations).await
 }
@@ -1,2 +1,4 @@
+pub mod forecasts;
 pub mod locations;
+pub mod stations;
 pub mod user;
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+
+type DateTimeUtc = chrono::DateTime<chrono::Utc>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ForecastBody {
+    pub forecast: Vec<ForecastPoint>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct ForecastPoint {
+    pub timestamp: DateTimeUtc,
+    pub cloud_cover: i16,
+}
@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct StationsBody {
+    pub stations: Vec<Station>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Station {
+    pub station: String,
+}
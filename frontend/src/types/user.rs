@@ -5,10 +5,10 @@ pub struct UnsubscribeUserWrapper {
     pub payload: String,
 }
 
-#[derive(Clone, Deserialize)]
-pub struct UnsubscribeParams {
-    pub user_id: String,
-    pub email: String,
+/// The signed token from a link in an email, which identifies the user.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TokenParams {
+    pub token: String,
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -21,13 +21,40 @@ pub struct UserRegisterInfo {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserRegisterBody {
     pub email: String,
+    pub alert_criterion: String,
+    pub min_visibility_score: i16,
+    pub max_cloud_cover: i16,
+    pub timezone: String,
+    pub quiet_hours_start: Option<String>,
+    pub quiet_hours_end: Option<String>,
+    pub alert_cooldown_minutes: i32,
+    pub notify_all_clear: bool,
+    pub locations: Vec<UserRegisterLocation>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UserRegisterLocation {
+    pub location_id: i64,
     pub alert_threshold: String,
-    pub locations: Vec<i64>,
+    pub max_cloud_cover: Option<i16>,
+}
+
+impl UserRegisterLocation {
+    pub fn new(location_id: i64) -> Self {
+        Self {
+            location_id,
+            alert_threshold: "yellow".to_string(),
+            max_cloud_cover: None,
+        }
+    }
 }
 
 impl UserRegisterBody {
     pub fn is_valid(&self) -> bool {
-        !self.email.is_empty() && !self.locations.is_empty()
+        !self.email.is_empty()
+            && !self.locations.is_empty()
+            && !self.timezone.is_empty()
+            && self.quiet_hours_start.is_some() == self.quiet_hours_end.is_some()
     }
 }
 
@@ -36,8 +63,74 @@ pub struct VerifyUserWrapper {
     pub payload: String,
 }
 
-#[derive(Clone, Deserialize)]
-pub struct VerifyUserParams {
-    pub user_id: String,
+/// The settings of an existing user, as loaded on the manage page.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct UserSettings {
+    pub email: String,
+    pub alert_criterion: String,
+    pub min_visibility_score: i16,
+    pub max_cloud_cover: i16,
+    pub timezone: String,
+    pub quiet_hours: Option<QuietHours>,
+    pub alert_cooldown_minutes: i32,
+    pub notify_all_clear: bool,
+    pub locations: Vec<UserSettingsLocation>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct UserSettingsLocation {
+    pub location_id: i64,
+    pub name: String,
+    pub alert_threshold: Option<String>,
+    pub max_cloud_cover: Option<i16>,
+}
+
+impl UserSettingsLocation {
+    pub fn to_register_location(&self) -> UserRegisterLocation {
+        UserRegisterLocation {
+            location_id: self.location_id,
+            alert_threshold: self
+                .alert_threshold
+                .clone()
+                .unwrap_or_else(|| "yellow".to_string()),
+            max_cloud_cover: self.max_cloud_cover,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct EmailBody {
     pub email: String,
 }
+
+#[derive(Debug, Serialize, Clone)]
+pub struct UserPreferencesBody {
+    pub token: String,
+    pub alert_criterion: String,
+    pub min_visibility_score: i16,
+    pub max_cloud_cover: i16,
+    pub timezone: String,
+    pub quiet_hours_start: Option<String>,
+    pub quiet_hours_end: Option<String>,
+    pub alert_cooldown_minutes: i32,
+    pub notify_all_clear: bool,
+}
+
+impl UserPreferencesBody {
+    pub fn is_valid(&self) -> bool {
+        !self.timezone.is_empty()
+            && self.quiet_hours_start.is_some() == self.quiet_hours_end.is_some()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct UserLocationsBody {
+    pub token: String,
+    pub locations: Vec<UserRegisterLocation>,
+}
@@ -0,0 +1,22 @@
+-- Activity data is now stored per station, e.g. AWN/LAN1, rather than for a
+-- single implicit station.
+CREATE TABLE stations (
+    station TEXT NOT NULL PRIMARY KEY,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+INSERT INTO stations (station, updated_at)
+SELECT 'AWN/LAN1', COALESCE(MAX(updated_at), CURRENT_TIMESTAMP)
+FROM activity_data_meta;
+
+DROP TABLE activity_data_meta;
+
+ALTER TABLE activity_data ADD COLUMN station TEXT NOT NULL DEFAULT 'AWN/LAN1';
+ALTER TABLE activity_data ALTER COLUMN station DROP DEFAULT;
+ALTER TABLE activity_data DROP CONSTRAINT activity_data_pkey;
+ALTER TABLE activity_data ADD PRIMARY KEY (station, timestamp);
+ALTER TABLE activity_data ADD FOREIGN KEY (station) REFERENCES stations (station) ON DELETE CASCADE;
+
+-- Record which site the stored alert level was reported by.
+ALTER TABLE alert_level ADD COLUMN site_id TEXT NOT NULL DEFAULT '';
+ALTER TABLE alert_level ALTER COLUMN site_id DROP DEFAULT;
@@ -0,0 +1,14 @@
+-- Store the alert level reported alongside each hourly activity value.
+ALTER TABLE activity_data ADD COLUMN alert_level alert_level_enum NOT NULL DEFAULT 'green';
+
+-- Existing data predates this column, so derive its alert level from the
+-- AuroraWatch UK thresholds in force at the time.
+UPDATE activity_data
+SET alert_level = CASE
+    WHEN value >= 200 THEN 'red'::alert_level_enum
+    WHEN value >= 100 THEN 'amber'::alert_level_enum
+    WHEN value >= 50 THEN 'yellow'::alert_level_enum
+    ELSE 'green'::alert_level_enum
+END;
+
+ALTER TABLE activity_data ALTER COLUMN alert_level DROP DEFAULT;
@@ -0,0 +1,10 @@
+-- The activity level, in nT, at which each alert level applies, as reported by
+-- each station.
+CREATE TABLE thresholds (
+    station TEXT NOT NULL PRIMARY KEY,
+    green REAL NOT NULL,
+    yellow REAL NOT NULL,
+    amber REAL NOT NULL,
+    red REAL NOT NULL,
+    FOREIGN KEY (station) REFERENCES stations (station) ON DELETE CASCADE
+);
@@ -0,0 +1,9 @@
+-- The forecast cloud cover at each location, typically for the next day.
+CREATE TABLE cloud_cover_forecasts (
+    location_id INTEGER NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL,
+    cloud_cover SMALLINT NOT NULL CHECK(cloud_cover BETWEEN 0 AND 100),
+    updated_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (location_id, timestamp),
+    FOREIGN KEY (location_id) REFERENCES locations (location_id) ON DELETE CASCADE
+);
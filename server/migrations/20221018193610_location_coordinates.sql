@@ -0,0 +1,29 @@
+-- The statement-level trigger from the initial migration runs an UPDATE of its
+-- own, which fires the trigger again without end, so no UPDATE of `locations`
+-- can succeed. Replace it with a row-level trigger which stamps the row itself.
+DROP TRIGGER update_location_updated_at ON locations;
+
+CREATE OR REPLACE FUNCTION update_location_updated_at_fn()
+    RETURNS trigger AS
+$$
+BEGIN
+    NEW.updated_at = CURRENT_TIMESTAMP;
+    RETURN NEW;
+END;
+$$
+LANGUAGE 'plpgsql';
+
+CREATE TRIGGER update_location_updated_at
+    BEFORE UPDATE
+    ON locations
+    FOR EACH ROW
+    EXECUTE PROCEDURE update_location_updated_at_fn();
+
+-- Weather providers other than OpenWeather locate places by their coordinates,
+-- rather than by OpenWeather's city id. These can be populated from the `coord`
+-- of each city in OpenWeather's city list.
+ALTER TABLE locations ADD COLUMN latitude DOUBLE PRECISION;
+ALTER TABLE locations ADD COLUMN longitude DOUBLE PRECISION;
+
+UPDATE locations SET latitude = 56.8198, longitude = -5.1052 WHERE location_id = 2649169;
+UPDATE locations SET latitude = 56.8901, longitude = -4.9222 WHERE location_id = 2637248;
@@ -0,0 +1,7 @@
+-- Users can choose to be alerted on the visibility score at their locations,
+-- rather than on the alert level alone.
+CREATE TYPE alert_criterion_enum AS ENUM ('alert_level', 'visibility_score');
+
+ALTER TABLE users ADD COLUMN alert_criterion alert_criterion_enum NOT NULL DEFAULT 'alert_level';
+ALTER TABLE users ADD COLUMN min_visibility_score SMALLINT NOT NULL DEFAULT 50
+    CHECK(min_visibility_score BETWEEN 0 AND 100);
@@ -0,0 +1,4 @@
+-- Users are only alerted when at least one of their locations has no more than
+-- this much cloud cover. The default of 100% keeps the existing behaviour.
+ALTER TABLE users ADD COLUMN max_cloud_cover SMALLINT NOT NULL DEFAULT 100
+    CHECK(max_cloud_cover BETWEEN 0 AND 100);
@@ -0,0 +1,14 @@
+-- Alert thresholds are chosen per location, rather than per user, and each
+-- location can override the user's maximum cloud cover.
+ALTER TABLE user_locations
+    ADD COLUMN alert_threshold alert_level_enum,
+    ADD COLUMN max_cloud_cover SMALLINT CHECK(max_cloud_cover BETWEEN 0 AND 100);
+
+UPDATE user_locations
+SET alert_threshold = users.alert_threshold
+FROM users
+WHERE users.user_id = user_locations.user_id;
+
+ALTER TABLE user_locations ALTER COLUMN alert_threshold SET NOT NULL;
+
+ALTER TABLE users DROP COLUMN alert_threshold;
@@ -0,0 +1,10 @@
+-- Users can choose not to be emailed during quiet hours, which are local times
+-- in their timezone, and how long to wait between alerts.
+ALTER TABLE users
+    ADD COLUMN timezone TEXT NOT NULL DEFAULT 'Europe/London',
+    ADD COLUMN quiet_hours_start TIME,
+    ADD COLUMN quiet_hours_end TIME,
+    ADD COLUMN alert_cooldown_minutes INTEGER NOT NULL DEFAULT 120
+        CHECK(alert_cooldown_minutes >= 0),
+    ADD CONSTRAINT quiet_hours_check
+        CHECK((quiet_hours_start IS NULL) = (quiet_hours_end IS NULL));
@@ -0,0 +1,5 @@
+-- Track the level each user was last alerted about, so that escalations can be
+-- sent straight away, and let users opt in to an email once activity subsides.
+ALTER TABLE users
+    ADD COLUMN last_notified_level alert_level_enum,
+    ADD COLUMN notify_all_clear BOOL NOT NULL DEFAULT false;
@@ -0,0 +1,19 @@
+-- A record of every alert, and all clear, sent to each user. The email address
+-- is kept so that the history survives users unsubscribing.
+CREATE TYPE notification_kind_enum AS ENUM ('alert', 'all_clear');
+CREATE TYPE notification_status_enum AS ENUM ('pending', 'sent', 'failed');
+
+CREATE TABLE notifications (
+    notification_id UUID NOT NULL DEFAULT gen_random_uuid() PRIMARY KEY,
+    user_id UUID REFERENCES users (user_id) ON DELETE SET NULL,
+    email CITEXT NOT NULL,
+    kind notification_kind_enum NOT NULL,
+    alert_level alert_level_enum NOT NULL,
+    site_id TEXT NOT NULL,
+    status notification_status_enum NOT NULL DEFAULT 'pending',
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX notifications_email_created_at_idx ON notifications (email, created_at);
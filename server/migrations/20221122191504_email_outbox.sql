@@ -0,0 +1,20 @@
+-- Emails are enqueued in the same transaction as the change which caused them,
+-- then delivered, with retries, by a worker. Emails which still can't be
+-- delivered after the maximum number of attempts are dead-lettered.
+CREATE TYPE outbox_status_enum AS ENUM ('pending', 'sent', 'dead');
+
+CREATE TABLE email_outbox (
+    email_id UUID NOT NULL DEFAULT gen_random_uuid() PRIMARY KEY,
+    notification_id UUID REFERENCES notifications (notification_id) ON DELETE SET NULL,
+    to_address TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status outbox_status_enum NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX email_outbox_pending_idx ON email_outbox (next_attempt_at) WHERE status = 'pending';
@@ -0,0 +1,3 @@
+-- A plain text alternative to the HTML body. Emails enqueued before this was
+-- added have none.
+ALTER TABLE email_outbox ADD COLUMN text_body TEXT;
@@ -0,0 +1,2 @@
+-- The one-click unsubscribe URL, sent in the List-Unsubscribe header
+ALTER TABLE email_outbox ADD COLUMN list_unsubscribe TEXT;
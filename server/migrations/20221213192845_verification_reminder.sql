@@ -0,0 +1,3 @@
+-- When an unverified user was reminded to verify their account, so that they're
+-- only reminded once
+ALTER TABLE users ADD COLUMN verification_reminder_sent_at TIMESTAMPTZ;
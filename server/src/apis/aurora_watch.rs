@@ -1,15 +1,18 @@
+use async_trait::async_trait;
 use chrono::TimeZone;
 use serde::{Deserialize, Serialize};
 
-use crate::common::{ActivityData, ActivityDataPoint, AlertLevel};
-use crate::types::DateTimeUtc;
+use super::geomagnetic::{CurrentAlertLevel, GeomagneticError, GeomagneticSource};
+use super::retry::{Provider, UpstreamError};
+use super::UpstreamClient;
+use crate::common::{ActivityData, ActivityDataPoint, AlertLevel, Thresholds};
 
 type Result<T> = std::result::Result<T, AuroraWatchError>;
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuroraWatchError {
     #[error("{0}")]
-    Request(#[from] reqwest::Error),
+    Upstream(#[from] UpstreamError),
     #[error("{0}")]
     Parse(String),
 }
@@ -43,7 +46,7 @@ impl ActivityDataExt for ActivityData {
     fn from_text(text: &str) -> Result<Self> {
         // Example text: https://aurorawatch.lancs.ac.uk/api/0.1/activity.txt
         let lines = text.lines();
-        let (_station, rest) = parse_single_value(lines)?;
+        let (station, rest) = parse_single_value(lines)?;
         let (_start_time, rest) = parse_single_value(rest)?;
         let (_end_time, rest) = parse_single_value(rest)?;
         let (updated_at, rest) = parse_single_value(rest).and_then(|(updated_at, rest)| {
@@ -51,16 +54,16 @@ impl ActivityDataExt for ActivityData {
             Ok((updated_at, rest))
         })?;
 
-        // Skip the 4 THRESHOLD definitions.
-        let rest = rest.skip(4);
+        let (thresholds, rest) = parse_thresholds(rest)?;
 
         let activities = parse_activities(rest)?;
 
         Ok(Self {
+            station: station.to_string(),
             updated_at,
-            // The api always returns 24 results. Missing entries have a value of nan, which we
-            // convert to 0.0
-            activities: activities.try_into().unwrap(),
+            // Missing entries have a value of nan, which have already been converted to 0.0.
+            activities,
+            thresholds: Some(thresholds),
         })
     }
 }
@@ -79,6 +82,61 @@ fn parse_single_value(mut lines: std::str::Lines) -> Result<(&str, std::str::Lin
     Ok((value, lines))
 }
 
+fn parse_threshold(line: &str) -> Result<(AlertLevel, f32)> {
+    // e.g. THRESHOLD 50 yellow
+    // Skip the THRESHOLD tag.
+    let mut parts = line.split_whitespace().skip(1);
+    let (first, second) = parts
+        .next()
+        .zip(parts.next())
+        .ok_or_else(|| AuroraWatchError::Parse(format!("Error parsing threshold: {line:?}")))?;
+
+    // Be lenient about the order of the level and value fields.
+    let (alert_level, value) = match first.parse::<AlertLevel>() {
+        Ok(alert_level) => (alert_level, second),
+        Err(_) => (
+            second
+                .parse::<AlertLevel>()
+                .map_err(|e| AuroraWatchError::Parse(format!("Error parsing threshold: {e}")))?,
+            first,
+        ),
+    };
+
+    Ok((alert_level, value.parse::<f32>()?))
+}
+
+fn parse_thresholds(mut lines: std::str::Lines) -> Result<(Thresholds, std::str::Lines)> {
+    // There is one THRESHOLD line for each of the 4 alert levels.
+    let (mut green, mut yellow, mut amber, mut red) = (None, None, None, None);
+    for _ in 0..4 {
+        let line = lines
+            .next()
+            .ok_or_else(|| AuroraWatchError::Parse("Error parsing thresholds".to_string()))?;
+        let (alert_level, value) = parse_threshold(line)?;
+        match alert_level {
+            AlertLevel::Green => green = Some(value),
+            AlertLevel::Yellow => yellow = Some(value),
+            AlertLevel::Amber => amber = Some(value),
+            AlertLevel::Red => red = Some(value),
+        }
+    }
+
+    match (green, yellow, amber, red) {
+        (Some(green), Some(yellow), Some(amber), Some(red)) => Ok((
+            Thresholds {
+                green,
+                yellow,
+                amber,
+                red,
+            },
+            lines,
+        )),
+        _ => Err(AuroraWatchError::Parse(
+            "Error parsing thresholds: not every alert level has a threshold".to_string(),
+        )),
+    }
+}
+
 fn parse_activity(line: &str) -> Result<ActivityDataPoint> {
     // e.g. ACTIVITY 2022-04-04T14:00:00+00 24.0 green
     // Skip the ACTIVITY tag.
@@ -104,26 +162,60 @@ fn parse_activity(line: &str) -> Result<ActivityDataPoint> {
         .parse::<f32>()
         .map(|val| if val.is_nan() { 0.0 } else { val })?;
 
-    // Implicitly skip the 4th item in the activity line, which is the alert level.
+    let alert_level = parts
+        .next()
+        .ok_or_else(|| {
+            AuroraWatchError::Parse(
+                "Error parsing activity datapoint: 'alert level' field not found".to_string(),
+            )
+        })?
+        .trim()
+        .parse::<AlertLevel>()
+        .map_err(|e| AuroraWatchError::Parse(format!("Error parsing activity datapoint: {e}")))?;
 
     Ok(ActivityDataPoint {
         timestamp: datetime,
         value,
+        alert_level,
     })
 }
 
-fn parse_activities(lines: std::iter::Skip<std::str::Lines>) -> Result<Vec<ActivityDataPoint>> {
+fn parse_activities(lines: std::str::Lines) -> Result<Vec<ActivityDataPoint>> {
     lines.map(parse_activity).collect()
 }
 
 /// Retrieve the latest activity data from the AuroraWatch API.
-pub async fn get_activity_data() -> Result<ActivityData> {
-    let client = reqwest::ClientBuilder::new()
-        .use_rustls_tls()
-        .build()
-        .unwrap();
-    let activity_data_url = "https://aurorawatch.lancs.ac.uk/api/0.1/activity.txt";
-    let response = client.get(activity_data_url).send().await?.text().await?;
+///
+/// `activity_path` is relative to the configured base URL, and determines which
+/// station the activity data is for.
+pub async fn get_activity_data(
+    client: &UpstreamClient,
+    activity_path: &str,
+) -> Result<ActivityData> {
+    let activity_data_url = format!(
+        "{}/{}",
+        client.settings.aurora_watch.activity_base_url, activity_path
+    );
+    let response = client
+        .get_text_conditional(Provider::AuroraWatch, &activity_data_url)
+        .await?;
+    let activity_data = ActivityData::from_text(&response)?;
+
+    Ok(activity_data)
+}
+
+/// Retrieve the archived activity data for a single day from the AuroraWatch
+/// API.
+pub async fn get_archived_activity_data(
+    client: &UpstreamClient,
+    date: chrono::NaiveDate,
+) -> Result<ActivityData> {
+    let archive_url = format!(
+        "{}/{}",
+        client.settings.aurora_watch.activity_base_url,
+        date.format(&client.settings.aurora_watch.archive_path_format)
+    );
+    let response = client.get_text(Provider::AuroraWatch, &archive_url).await?;
     let activity_data = ActivityData::from_text(&response)?;
 
     Ok(activity_data)
@@ -153,14 +245,6 @@ struct CurrentStatus {
     site_status: SiteStatus,
 }
 
-/// The current alert level derived from the AuroraWatch API.
-#[derive(Debug, Deserialize)]
-pub struct CurrentAlertLevel {
-    pub level: AlertLevel,
-    pub updated_at: DateTimeUtc,
-    pub site_id: String,
-}
-
 impl From<CurrentStatus> for CurrentAlertLevel {
     fn from(current_status: CurrentStatus) -> Self {
         Self {
@@ -172,13 +256,76 @@ impl From<CurrentStatus> for CurrentAlertLevel {
 }
 
 /// Retrieve the current alert level from the AuroraWatch API.
-pub async fn get_alert_level() -> Result<CurrentAlertLevel> {
-    let status_url = "https://aurorawatch-api.lancs.ac.uk/0.2/status/current-status.xml";
-    let client = reqwest::ClientBuilder::new()
-        .use_rustls_tls()
-        .build()
-        .unwrap();
-    let xml_response = client.get(status_url).send().await?.text().await?;
+pub async fn get_alert_level(client: &UpstreamClient) -> Result<CurrentAlertLevel> {
+    let status_url = format!(
+        "{}/status/current-status.xml",
+        client.settings.aurora_watch.status_base_url
+    );
+    let xml_response = client
+        .get_text_conditional(Provider::AuroraWatch, &status_url)
+        .await?;
     let status: CurrentAlertLevel = quick_xml::de::from_str::<CurrentStatus>(&xml_response)?.into();
     Ok(status)
 }
+
+/// A single AuroraWatch UK station, run by Lancaster University, as a source of
+/// geomagnetic data.
+#[derive(Debug)]
+pub struct AuroraWatchSource {
+    client: UpstreamClient,
+    activity_path: String,
+}
+
+impl AuroraWatchSource {
+    pub fn new(client: UpstreamClient, activity_path: &str) -> Self {
+        Self {
+            client,
+            activity_path: activity_path.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl GeomagneticSource for AuroraWatchSource {
+    fn name(&self) -> &'static str {
+        "AuroraWatch UK"
+    }
+
+    async fn history(&self) -> std::result::Result<ActivityData, GeomagneticError> {
+        Ok(get_activity_data(&self.client, &self.activity_path).await?)
+    }
+
+    async fn current_alert_level(
+        &self,
+    ) -> std::result::Result<CurrentAlertLevel, GeomagneticError> {
+        Ok(get_alert_level(&self.client).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_activity_data_from_text() {
+        let text = "STATION AWN/LAN1
+START_TIME 2022-04-04T14:00:00+00
+END_TIME 2022-04-04T16:00:00+00
+CREATION_TIME 2022-04-04T15:12:31+00
+THRESHOLD 0 green
+THRESHOLD 50 yellow
+THRESHOLD 100 amber
+THRESHOLD 200 red
+ACTIVITY 2022-04-04T14:00:00+00 24.0 green
+ACTIVITY 2022-04-04T15:00:00+00 nan green";
+
+        let activity_data = ActivityData::from_text(text).unwrap();
+
+        assert_eq!(activity_data.station, "AWN/LAN1");
+        assert_eq!(activity_data.thresholds, Some(Thresholds::default()));
+        assert_eq!(activity_data.activities.len(), 2);
+        assert_eq!(activity_data.activities[0].value, 24.0);
+        assert_eq!(activity_data.activities[1].value, 0.0);
+        assert_eq!(activity_data.activities[1].alert_level, AlertLevel::Green);
+    }
+}
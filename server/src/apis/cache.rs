@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use reqwest::header::{self, HeaderMap, HeaderValue};
+
+/// A previously fetched response body, along with the validators needed to
+/// make a conditional request for it.
+#[derive(Debug)]
+struct CachedResponse {
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    body: String,
+}
+
+/// The latest response for each polled URL, so that unchanged documents aren't
+/// downloaded again.
+///
+/// Clones share the same entries.
+#[derive(Clone, Debug, Default)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<HashMap<String, CachedResponse>>>,
+}
+
+impl ResponseCache {
+    /// The `If-None-Match` and `If-Modified-Since` headers for a request to `url`,
+    /// if it has been fetched before.
+    pub fn conditional_headers(&self, url: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(cached) = self.entries.lock().unwrap().get(url) {
+            if let Some(etag) = &cached.etag {
+                headers.insert(header::IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                headers.insert(header::IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+        headers
+    }
+
+    pub fn body(&self, url: &str) -> Option<String> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(url)
+            .map(|cached| cached.body.clone())
+    }
+
+    /// Store a response body, if the response carried any validators.
+    pub fn store(&self, url: &str, headers: &HeaderMap, body: &str) {
+        let etag = headers.get(header::ETAG).cloned();
+        let last_modified = headers.get(header::LAST_MODIFIED).cloned();
+        let mut entries = self.entries.lock().unwrap();
+        if etag.is_none() && last_modified.is_none() {
+            entries.remove(url);
+            return;
+        }
+
+        entries.insert(
+            url.to_string(),
+            CachedResponse {
+                etag,
+                last_modified,
+                body: body.to_string(),
+            },
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use serde::Deserialize;
+
+use super::aurora_watch::{AuroraWatchError, AuroraWatchSource};
+use super::noaa_swpc::{NoaaSwpcError, NoaaSwpcSource};
+use super::UpstreamClient;
+use crate::common::{ActivityData, AlertLevel};
+use crate::configuration::GeomagneticSourceKind;
+use crate::types::DateTimeUtc;
+
+type Result<T> = std::result::Result<T, GeomagneticError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GeomagneticError {
+    #[error("AuroraWatch UK: {0}")]
+    AuroraWatch(#[from] AuroraWatchError),
+    #[error("NOAA SWPC: {0}")]
+    NoaaSwpc(#[from] NoaaSwpcError),
+    #[error("no geomagnetic data sources are configured")]
+    NoSources,
+    #[error("no activity data was returned by any source")]
+    NoActivityData,
+}
+
+/// The current alert level, as reported by a geomagnetic data source.
+#[derive(Debug, Deserialize)]
+pub struct CurrentAlertLevel {
+    pub level: AlertLevel,
+    pub updated_at: DateTimeUtc,
+    pub site_id: String,
+}
+
+/// A provider of geomagnetic activity data and alert levels.
+#[async_trait]
+pub trait GeomagneticSource: std::fmt::Debug + Send + Sync {
+    /// A short, human readable name for the source, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Retrieve the recent hourly activity data, typically the last 24 hours.
+    async fn history(&self) -> Result<ActivityData>;
+
+    /// Retrieve the current alert level.
+    async fn current_alert_level(&self) -> Result<CurrentAlertLevel>;
+}
+
+/// An ordered list of geomagnetic data sources.
+///
+/// Each request is made against the sources in order, falling back to the next
+/// source whenever one fails, so that a single upstream outage does not stop
+/// activity updates or alerts.
+#[derive(Debug)]
+pub struct GeomagneticSources {
+    sources: Vec<Box<dyn GeomagneticSource>>,
+}
+
+impl GeomagneticSources {
+    /// Build the sources listed in the configuration, in order of preference.
+    pub fn from_settings(client: &UpstreamClient) -> Self {
+        let mut sources: Vec<Box<dyn GeomagneticSource>> = vec![];
+        for kind in &client.settings.geomagnetic_sources {
+            match kind {
+                GeomagneticSourceKind::AuroraWatch => {
+                    // Each AuroraWatch UK station is published as a separate document.
+                    for activity_path in &client.settings.aurora_watch.activity_paths {
+                        sources.push(Box::new(AuroraWatchSource::new(
+                            client.clone(),
+                            activity_path,
+                        )));
+                    }
+                }
+                GeomagneticSourceKind::NoaaSwpc => {
+                    sources.push(Box::new(NoaaSwpcSource::new(client.clone())));
+                }
+            }
+        }
+
+        Self { sources }
+    }
+
+    /// Retrieve the activity data from every source.
+    ///
+    /// Unlike `history`, which stops at the first source to respond, this keeps
+    /// the data for every station up to date. Sources which fail are logged
+    /// and skipped.
+    pub async fn all_histories(&self) -> Vec<ActivityData> {
+        let mut histories = vec![];
+        for source in &self.sources {
+            match source.history().await {
+                Ok(history) => histories.push(history),
+                Err(e) => {
+                    tracing::error!("error fetching activity data from {}: {e}", source.name());
+                }
+            }
+        }
+        histories
+    }
+}
+
+#[async_trait]
+impl GeomagneticSource for GeomagneticSources {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    async fn history(&self) -> Result<ActivityData> {
+        if self.sources.is_empty() {
+            return Err(GeomagneticError::NoSources);
+        }
+        for source in &self.sources {
+            match source.history().await {
+                Ok(history) if !history.activities.is_empty() => return Ok(history),
+                Ok(_) => {
+                    tracing::warn!("no activity data returned from {}", source.name());
+                }
+                Err(e) => {
+                    tracing::warn!("error fetching activity data from {}: {e}", source.name());
+                }
+            }
+        }
+        // Each source's error has been logged above.
+        Err(GeomagneticError::NoActivityData)
+    }
+
+    async fn current_alert_level(&self) -> Result<CurrentAlertLevel> {
+        let mut last_error = GeomagneticError::NoSources;
+        for source in &self.sources {
+            match source.current_alert_level().await {
+                Ok(alert_level) => return Ok(alert_level),
+                Err(e) => {
+                    tracing::warn!("error fetching alert level from {}: {e}", source.name());
+                    last_error = e;
+                }
+            }
+        }
+        Err(last_error)
+    }
+}
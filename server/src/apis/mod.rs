@@ -1,2 +1,157 @@
 pub mod aurora_watch;
+pub mod cache;
+pub mod geomagnetic;
+pub mod noaa_swpc;
+pub mod open_meteo;
 pub mod open_weather;
+pub mod retry;
+pub mod weather;
+
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+use self::cache::ResponseCache;
+use self::retry::{is_transient, CircuitBreakers, Provider, RetryPolicy, UpstreamError};
+use crate::configuration::UpstreamSettings;
+
+/// A HTTP client, plus the base URLs, for talking to all third party APIs.
+///
+/// The underlying `reqwest::Client` holds a connection pool internally, so
+/// this is cheap to clone and should be shared rather than rebuilt per request.
+#[derive(Clone, Debug)]
+pub struct UpstreamClient {
+    pub http: reqwest::Client,
+    pub settings: UpstreamSettings,
+    retry_policy: RetryPolicy,
+    circuit_breakers: CircuitBreakers,
+    cache: ResponseCache,
+}
+
+impl UpstreamClient {
+    pub fn new(settings: &UpstreamSettings) -> Result<Self, reqwest::Error> {
+        let http = reqwest::ClientBuilder::new()
+            .use_rustls_tls()
+            .user_agent(settings.user_agent.clone())
+            .timeout(settings.timeout())
+            .build()?;
+
+        Ok(Self {
+            http,
+            settings: settings.clone(),
+            retry_policy: RetryPolicy::new(&settings.retry),
+            circuit_breakers: CircuitBreakers::new(&settings.retry),
+            cache: ResponseCache::default(),
+        })
+    }
+
+    /// Send a GET request to one of the providers' APIs, and decode the JSON
+    /// response.
+    ///
+    /// Transient failures (timeouts, connection errors, 5xx and 429 responses)
+    /// are retried with exponential backoff. If the provider keeps failing, its
+    /// circuit breaker opens and further requests fail fast for a while.
+    ///
+    /// Secrets such as API keys should be passed in `query`, rather than in the
+    /// `url`; no error returned from here includes the URL.
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        provider: Provider,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<T, UpstreamError> {
+        let body = self
+            .get_with_headers(provider, url, query, HeaderMap::new())
+            .await?
+            .json::<T>()
+            .await?;
+        Ok(body)
+    }
+
+    /// Send a GET request to one of the providers' APIs, and return the body as
+    /// text.
+    pub async fn get_text(&self, provider: Provider, url: &str) -> Result<String, UpstreamError> {
+        let body = self
+            .get_with_headers(provider, url, &[], HeaderMap::new())
+            .await?
+            .text()
+            .await?;
+        Ok(body)
+    }
+
+    /// Fetch a document which is polled regularly, using a conditional request.
+    ///
+    /// If the document hasn't changed since it was last fetched, the upstream
+    /// responds with `304 Not Modified` and the cached body is returned instead.
+    pub async fn get_text_conditional(
+        &self,
+        provider: Provider,
+        url: &str,
+    ) -> Result<String, UpstreamError> {
+        let headers = self.cache.conditional_headers(url);
+        let response = self.get_with_headers(provider, url, &[], headers).await?;
+
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(body) = self.cache.body(url) {
+                tracing::debug!("{provider} document at {url} not modified");
+                return Ok(body);
+            }
+            // The cache should never be missing a body we sent validators for, but
+            // fall back to an unconditional request just in case.
+            return self.get_text(provider, url).await;
+        }
+
+        let response_headers = response.headers().clone();
+        let body = response.text().await?;
+        self.cache.store(url, &response_headers, &body);
+        Ok(body)
+    }
+
+    async fn get_with_headers(
+        &self,
+        provider: Provider,
+        url: &str,
+        query: &[(&str, &str)],
+        headers: HeaderMap,
+    ) -> Result<reqwest::Response, UpstreamError> {
+        let circuit_breaker = self.circuit_breakers.for_provider(provider);
+        circuit_breaker.check()?;
+
+        let mut attempt = 1;
+        loop {
+            let response = self
+                .http
+                .get(url)
+                .query(query)
+                .headers(headers.clone())
+                .send()
+                .await
+                .and_then(reqwest::Response::error_for_status);
+
+            match response {
+                Ok(response) => {
+                    circuit_breaker.record_success();
+                    return Ok(response);
+                }
+                Err(e) if is_transient(&e) && attempt < self.retry_policy.max_attempts => {
+                    let backoff = self.retry_policy.backoff(attempt);
+                    let e = UpstreamError::from(e);
+                    tracing::warn!(
+                        "attempt {attempt} of request to {provider} failed, retrying in {backoff:?}: {e}"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    // Any other error (e.g. a 404) still shows that the provider is up.
+                    if is_transient(&e) {
+                        circuit_breaker.record_failure();
+                    } else {
+                        circuit_breaker.record_success();
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+    }
+}
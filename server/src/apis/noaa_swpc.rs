@@ -0,0 +1,271 @@
+use async_trait::async_trait;
+use chrono::{TimeZone, Timelike};
+
+use super::geomagnetic::{CurrentAlertLevel, GeomagneticError, GeomagneticSource};
+use super::retry::{Provider, UpstreamError};
+use super::UpstreamClient;
+use crate::common::{ActivityData, ActivityDataPoint, AlertLevel, Thresholds};
+use crate::types::DateTimeUtc;
+
+type Result<T> = std::result::Result<T, NoaaSwpcError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum NoaaSwpcError {
+    #[error("{0}")]
+    Upstream(#[from] UpstreamError),
+    #[error("{0}")]
+    Parse(String),
+}
+
+impl std::convert::From<chrono::ParseError> for NoaaSwpcError {
+    fn from(e: chrono::ParseError) -> Self {
+        NoaaSwpcError::Parse(format!("error parsing datetime: {e}"))
+    }
+}
+
+impl std::convert::From<serde_json::Error> for NoaaSwpcError {
+    fn from(e: serde_json::Error) -> Self {
+        NoaaSwpcError::Parse(format!("error parsing JSON: {e}"))
+    }
+}
+
+impl std::convert::From<std::num::ParseFloatError> for NoaaSwpcError {
+    fn from(e: std::num::ParseFloatError) -> Self {
+        NoaaSwpcError::Parse(format!("error parsing Kp value: {e}"))
+    }
+}
+
+/// The Kp index is derived from a global network of stations, so all data from
+/// this source is attributed to a single pseudo-station.
+const STATION: &str = "SWPC/Kp";
+
+/// The equivalent planetary amplitude (ap) for each Kp value, from 0o through
+/// to 9o in steps of a third.
+///
+/// This is the standard Kp to ap conversion table (Bartels, 1957), as published
+/// by GFZ Potsdam, who maintain the Kp index:
+/// <https://www.gfz-potsdam.de/en/section/geomagnetism/data-products-services/geomagnetic-kp-index>
+const AP_EQUIVALENTS: [f32; 28] = [
+    0., 2., 3., 4., 5., 6., 7., 9., 12., 15., 18., 22., 27., 32., 39., 48., 56., 67., 80., 94.,
+    111., 132., 154., 179., 207., 236., 300., 400.,
+];
+
+/// A single 3-hourly planetary Kp index reading.
+#[derive(Debug)]
+struct KpReading {
+    time_tag: DateTimeUtc,
+    kp: f32,
+}
+
+impl KpReading {
+    /// Approximate the disturbance, in nT, represented by this reading.
+    ///
+    /// By definition, ap is roughly half the range of the disturbance in nT at a
+    /// station at about 50 degrees geomagnetic latitude, so doubling it gives a
+    /// figure on the same scale as the activity reported by AuroraWatch UK.
+    ///
+    /// This is only an approximation: Kp covers 3 hours rather than 1, and
+    /// Lancaster is further north than the reference latitude, so it can differ
+    /// from what AuroraWatch UK measures by up to a factor of 2 either way.
+    /// Under `Thresholds::default()` it puts Kp 5, 6 and 7 at yellow, amber and
+    /// red, in line with NOAA's G1, G2 and G3 geomagnetic storm levels.
+    fn activity(&self) -> f32 {
+        let index = (self.kp * 3.0).round().clamp(0.0, 27.0) as usize;
+        AP_EQUIVALENTS[index] * 2.0
+    }
+
+    /// Derive an alert level using the same nT thresholds as AuroraWatch UK.
+    fn alert_level(&self) -> AlertLevel {
+        Thresholds::default().alert_level(self.activity())
+    }
+}
+
+fn parse_kp_index(rows: Vec<Vec<String>>) -> Result<Vec<KpReading>> {
+    // e.g. [["time_tag","Kp","a_running","station_count"],
+    //       ["2022-09-10 00:00:00.000","2.33","9","8"], ...]
+    // Skip the header row.
+    rows.iter()
+        .skip(1)
+        .map(|row| {
+            let (time_tag, kp) = match row.as_slice() {
+                [time_tag, kp, ..] => (time_tag, kp),
+                _ => {
+                    return Err(NoaaSwpcError::Parse(
+                        "Error parsing Kp reading: expected at least 2 fields".to_string(),
+                    ))
+                }
+            };
+
+            Ok(KpReading {
+                time_tag: chrono::Utc.datetime_from_str(time_tag, "%F %T%.f")?,
+                kp: kp.parse::<f32>()?,
+            })
+        })
+        .collect()
+}
+
+/// Expand the 3-hourly readings into the 24 hourly data points ending at the
+/// current hour.
+fn to_activity_data(readings: &[KpReading], now: DateTimeUtc) -> Result<ActivityData> {
+    let latest = readings
+        .iter()
+        .max_by_key(|reading| reading.time_tag)
+        .ok_or_else(|| NoaaSwpcError::Parse("No Kp readings found".to_string()))?;
+
+    let end = now.date().and_hms(now.time().hour(), 0, 0);
+    let activities = (0..24)
+        .rev()
+        .map(|hours_ago| {
+            let timestamp = end - chrono::Duration::hours(hours_ago);
+            // Hours which aren't covered by a reading are reported as 0.0, in line with
+            // AuroraWatch UK's missing values.
+            let reading = readings.iter().find(|reading| {
+                reading.time_tag <= timestamp
+                    && timestamp < reading.time_tag + chrono::Duration::hours(3)
+            });
+            ActivityDataPoint {
+                timestamp,
+                value: reading.map(KpReading::activity).unwrap_or(0.0),
+                alert_level: reading
+                    .map(KpReading::alert_level)
+                    .unwrap_or(AlertLevel::Green),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    Ok(ActivityData {
+        station: STATION.to_string(),
+        updated_at: latest.time_tag,
+        activities,
+        thresholds: None,
+    })
+}
+
+/// Retrieve the past week of 3-hourly planetary Kp readings from NOAA SWPC.
+async fn get_kp_index(client: &UpstreamClient) -> Result<Vec<KpReading>> {
+    let url = format!(
+        "{}/products/noaa-planetary-k-index.json",
+        client.settings.noaa_swpc.base_url
+    );
+    let response = client
+        .get_text_conditional(Provider::NoaaSwpc, &url)
+        .await?;
+    let rows = serde_json::from_str::<Vec<Vec<String>>>(&response)?;
+
+    parse_kp_index(rows)
+}
+
+/// The NOAA Space Weather Prediction Center, as a source of global geomagnetic
+/// data based on the planetary Kp index.
+#[derive(Debug)]
+pub struct NoaaSwpcSource {
+    client: UpstreamClient,
+}
+
+impl NoaaSwpcSource {
+    pub fn new(client: UpstreamClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl GeomagneticSource for NoaaSwpcSource {
+    fn name(&self) -> &'static str {
+        "NOAA SWPC"
+    }
+
+    async fn history(&self) -> std::result::Result<ActivityData, GeomagneticError> {
+        let readings = get_kp_index(&self.client).await?;
+        Ok(to_activity_data(&readings, chrono::Utc::now())?)
+    }
+
+    async fn current_alert_level(
+        &self,
+    ) -> std::result::Result<CurrentAlertLevel, GeomagneticError> {
+        let readings = get_kp_index(&self.client).await?;
+        let latest = readings
+            .iter()
+            .max_by_key(|reading| reading.time_tag)
+            .ok_or_else(|| NoaaSwpcError::Parse("No Kp readings found".to_string()))?;
+
+        Ok(CurrentAlertLevel {
+            level: latest.alert_level(),
+            updated_at: latest.time_tag,
+            site_id: STATION.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(rows: &[[&str; 4]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|field| field.to_string()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_kp_index() {
+        let readings = parse_kp_index(rows(&[
+            ["time_tag", "Kp", "a_running", "station_count"],
+            ["2022-09-10 00:00:00.000", "2.33", "9", "8"],
+            ["2022-09-10 03:00:00.000", "5.67", "67", "8"],
+        ]))
+        .unwrap();
+
+        assert_eq!(readings.len(), 2);
+        assert_eq!(
+            readings[1].time_tag,
+            chrono::Utc.ymd(2022, 9, 10).and_hms(3, 0, 0)
+        );
+        assert_eq!(readings[0].activity(), 18.);
+        assert_eq!(readings[0].alert_level(), AlertLevel::Green);
+        assert_eq!(readings[1].activity(), 134.);
+        assert_eq!(readings[1].alert_level(), AlertLevel::Amber);
+    }
+
+    #[test]
+    fn test_kp_storm_levels_map_to_alert_levels() {
+        let alert_level = |kp| {
+            KpReading {
+                time_tag: chrono::Utc.ymd(2022, 9, 10).and_hms(0, 0, 0),
+                kp,
+            }
+            .alert_level()
+        };
+
+        assert_eq!(alert_level(3.67), AlertLevel::Green);
+        assert_eq!(alert_level(5.), AlertLevel::Yellow);
+        assert_eq!(alert_level(6.), AlertLevel::Amber);
+        assert_eq!(alert_level(7.), AlertLevel::Red);
+    }
+
+    #[test]
+    fn test_to_activity_data_spans_24_hours() {
+        let readings = parse_kp_index(rows(&[
+            ["time_tag", "Kp", "a_running", "station_count"],
+            ["2022-09-10 00:00:00.000", "4.00", "27", "8"],
+        ]))
+        .unwrap();
+        let now = chrono::Utc.ymd(2022, 9, 10).and_hms(2, 30, 0);
+
+        let activity_data = to_activity_data(&readings, now).unwrap();
+
+        let first = &activity_data.activities[0];
+        let last = &activity_data.activities[23];
+        assert_eq!(
+            first.timestamp,
+            chrono::Utc.ymd(2022, 9, 9).and_hms(3, 0, 0)
+        );
+        assert_eq!(first.value, 0.);
+        assert_eq!(first.alert_level, AlertLevel::Green);
+        assert_eq!(
+            last.timestamp,
+            chrono::Utc.ymd(2022, 9, 10).and_hms(2, 0, 0)
+        );
+        assert_eq!(last.value, 54.);
+        assert_eq!(last.alert_level, AlertLevel::Yellow);
+    }
+}
@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use chrono::{TimeZone, Timelike};
+use serde::Deserialize;
+
+use super::retry::{Provider, UpstreamError};
+use super::weather::{CloudCoverForecast, Weather, WeatherError, WeatherLocation, WeatherProvider};
+use super::UpstreamClient;
+use crate::types::DateTimeUtc;
+
+type Result<T> = std::result::Result<T, OpenMeteoError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum OpenMeteoError {
+    #[error("{0}")]
+    Upstream(#[from] UpstreamError),
+    #[error("{0}")]
+    Parse(String),
+    #[error("location {0} has no coordinates")]
+    MissingCoordinates(i32),
+}
+
+impl std::convert::From<chrono::ParseError> for OpenMeteoError {
+    fn from(e: chrono::ParseError) -> Self {
+        OpenMeteoError::Parse(format!("error parsing datetime: {e}"))
+    }
+}
+
+/// The number of hourly forecasts to keep, covering the next 24 hours.
+const FORECAST_HOURS: usize = 24;
+
+#[derive(Debug, Deserialize)]
+struct CurrentWeather {
+    weathercode: u8,
+}
+
+#[derive(Debug, Deserialize)]
+struct Hourly {
+    // Times are in UTC, without an offset, e.g. "2022-10-11T18:00".
+    time: Vec<String>,
+    // Cloud cover is reported as an integer percentage.
+    cloudcover: Vec<i16>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastBody {
+    current_weather: CurrentWeather,
+    hourly: Hourly,
+}
+
+/// A description of a WMO weather interpretation code, as used by Open-Meteo.
+fn describe_weather_code(code: u8) -> &'static str {
+    match code {
+        0 => "clear sky",
+        1 => "mainly clear",
+        2 => "partly cloudy",
+        3 => "overcast",
+        45 | 48 => "fog",
+        51..=57 => "drizzle",
+        61..=67 => "rain",
+        71..=77 => "snow",
+        80..=82 => "rain showers",
+        85 | 86 => "snow showers",
+        95..=99 => "thunderstorm",
+        _ => "unknown",
+    }
+}
+
+fn parse_hourly(hourly: &Hourly) -> Result<Vec<CloudCoverForecast>> {
+    hourly
+        .time
+        .iter()
+        .zip(&hourly.cloudcover)
+        .map(|(time, cloud_cover)| {
+            let timestamp = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")?;
+            Ok(CloudCoverForecast {
+                timestamp: chrono::Utc.from_utc_datetime(&timestamp),
+                cloud_cover: *cloud_cover,
+            })
+        })
+        .collect()
+}
+
+/// Split the hourly data into the current weather, and the forecast for the
+/// following hours.
+fn to_weather(body: ForecastBody, now: DateTimeUtc) -> Result<(Weather, Vec<CloudCoverForecast>)> {
+    let start_of_hour = now.date().and_hms(now.time().hour(), 0, 0);
+    let mut hours = parse_hourly(&body.hourly)?
+        .into_iter()
+        .filter(|forecast| forecast.timestamp >= start_of_hour);
+
+    let current = hours
+        .next()
+        .ok_or_else(|| OpenMeteoError::Parse("no hourly data for the current hour".to_string()))?;
+    let weather = Weather {
+        description: describe_weather_code(body.current_weather.weathercode).to_string(),
+        cloud_cover: current.cloud_cover,
+        // The coordinates were supplied by us, so there's nothing new to report.
+        latitude: None,
+        longitude: None,
+    };
+
+    Ok((weather, hours.take(FORECAST_HOURS).collect()))
+}
+
+/// The latitude and longitude of a location, which Open-Meteo needs to find it.
+fn coordinates(location: &WeatherLocation) -> Result<(f64, f64)> {
+    match (location.latitude, location.longitude) {
+        (Some(latitude), Some(longitude)) => Ok((latitude, longitude)),
+        _ => Err(OpenMeteoError::MissingCoordinates(location.location_id)),
+    }
+}
+
+/// Retrieve the current weather and the hourly forecast for the next 2 days.
+async fn get_forecast(client: &UpstreamClient, location: &WeatherLocation) -> Result<ForecastBody> {
+    let (latitude, longitude) = coordinates(location)?;
+
+    let url = format!(
+        "{}/forecast?latitude={}&longitude={}&current_weather=true&hourly=cloudcover&forecast_days=2&timezone=UTC",
+        client.settings.open_meteo.base_url, latitude, longitude
+    );
+
+    let body = client
+        .get_json::<ForecastBody>(Provider::OpenMeteo, &url, &[])
+        .await?;
+    Ok(body)
+}
+
+/// Open-Meteo, which needs no API key but locates places by their coordinates.
+#[derive(Debug)]
+pub struct OpenMeteoProvider {
+    client: UpstreamClient,
+}
+
+impl OpenMeteoProvider {
+    pub fn new(client: UpstreamClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for OpenMeteoProvider {
+    fn name(&self) -> &'static str {
+        "Open-Meteo"
+    }
+
+    async fn current_weather(
+        &self,
+        location: &WeatherLocation,
+    ) -> std::result::Result<Weather, WeatherError> {
+        let body = get_forecast(&self.client, location).await?;
+        let (weather, _forecast) = to_weather(body, chrono::Utc::now())?;
+        Ok(weather)
+    }
+
+    async fn cloud_cover_forecast(
+        &self,
+        location: &WeatherLocation,
+    ) -> std::result::Result<Vec<CloudCoverForecast>, WeatherError> {
+        let body = get_forecast(&self.client, location).await?;
+        let (_weather, forecast) = to_weather(body, chrono::Utc::now())?;
+        Ok(forecast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_weather_starts_at_current_hour() {
+        let body = ForecastBody {
+            current_weather: CurrentWeather { weathercode: 3 },
+            hourly: Hourly {
+                time: vec![
+                    "2022-10-11T17:00".to_string(),
+                    "2022-10-11T18:00".to_string(),
+                    "2022-10-11T19:00".to_string(),
+                ],
+                cloudcover: vec![100, 80, 20],
+            },
+        };
+        let now = chrono::Utc.ymd(2022, 10, 11).and_hms(18, 30, 0);
+
+        let (weather, forecast) = to_weather(body, now).unwrap();
+
+        assert_eq!(weather.description, "overcast");
+        assert_eq!(weather.cloud_cover, 80);
+        assert_eq!(forecast.len(), 1);
+        assert_eq!(
+            forecast[0].timestamp,
+            chrono::Utc.ymd(2022, 10, 11).and_hms(19, 0, 0)
+        );
+        assert_eq!(forecast[0].cloud_cover, 20);
+    }
+
+    #[test]
+    fn test_location_without_coordinates() {
+        let location = WeatherLocation {
+            location_id: 2649169,
+            latitude: None,
+            longitude: None,
+        };
+
+        assert!(matches!(
+            coordinates(&location),
+            Err(OpenMeteoError::MissingCoordinates(2649169))
+        ));
+    }
+}
@@ -1,11 +1,19 @@
+use async_trait::async_trait;
+use chrono::TimeZone;
 use serde::Deserialize;
 
+use super::retry::{Provider, UpstreamError};
+use super::weather::{CloudCoverForecast, Weather, WeatherError, WeatherLocation, WeatherProvider};
+use super::UpstreamClient;
+
 type Result<T> = std::result::Result<T, OpenWeatherError>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum OpenWeatherError {
-    #[error("error")]
-    Request(#[from] reqwest::Error),
+    #[error("{0}")]
+    Upstream(#[from] UpstreamError),
+    #[error("location {0} has no coordinates")]
+    MissingCoordinates(i32),
 }
 
 #[derive(Debug, Deserialize)]
@@ -18,51 +26,200 @@ struct WeatherDetail {
     description: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct Coord {
+    lat: f64,
+    lon: f64,
+}
+
 #[derive(Debug, Deserialize)]
 struct WeatherBody {
+    coord: Coord,
     // Cloud cover is reported as an integer percentage.
     clouds: Clouds,
     // There is always at least 1 primary weather field, plus optional extras.
     weather: Vec<WeatherDetail>,
-    // What OpenWeather calls the city_id is an unsigned integer which fits within a u32.
-    id: u32,
-}
-
-pub struct Weather {
-    pub location_id: u32,
-    pub description: String,
-    pub cloud_cover: i16,
 }
 
 impl std::convert::From<WeatherBody> for Weather {
     fn from(weather_body: WeatherBody) -> Self {
         Self {
-            location_id: weather_body.id,
             // OpenWeather returns a weather field which contains 1 or more entries, with the
             // first entry being the primary weather conditions. There should always, therefore,
             // be a `.first()` entry.
             description: weather_body.weather.first().unwrap().description.clone(),
             cloud_cover: weather_body.clouds.all,
+            latitude: Some(weather_body.coord.lat),
+            longitude: Some(weather_body.coord.lon),
         }
     }
 }
 
-pub async fn get_weather(location_id: i32, api_key: &str) -> Result<Weather> {
-    let url = format!(
-        "https://api.openweathermap.org/data/2.5/weather?id={}&appid={}",
-        location_id, api_key
-    );
+/// The number of hourly forecasts to keep, covering the next 24 hours.
+const FORECAST_HOURS: usize = 24;
+
+#[derive(Debug, Deserialize)]
+struct HourlyForecast {
+    // The start of the hour, as a unix timestamp.
+    dt: i64,
+    // Cloud cover is reported as an integer percentage.
+    clouds: i16,
+}
+
+#[derive(Debug, Deserialize)]
+struct ForecastBody {
+    // The One Call API returns the next 48 hours, starting with the current hour.
+    hourly: Vec<HourlyForecast>,
+}
+
+impl std::convert::From<ForecastBody> for Vec<CloudCoverForecast> {
+    fn from(forecast_body: ForecastBody) -> Self {
+        forecast_body
+            .hourly
+            .into_iter()
+            // Skip the current hour, to leave the forecast for the following hours.
+            .skip(1)
+            .take(FORECAST_HOURS)
+            .filter_map(|hour| {
+                Some(CloudCoverForecast {
+                    timestamp: chrono::Utc.timestamp_opt(hour.dt, 0).single()?,
+                    cloud_cover: hour.clouds,
+                })
+            })
+            .collect()
+    }
+}
+
+pub async fn get_weather(
+    client: &UpstreamClient,
+    location_id: i32,
+    api_key: &str,
+) -> Result<Weather> {
+    let url = format!("{}/weather", client.settings.open_weather.base_url);
+    let location_id = location_id.to_string();
 
-    let client = reqwest::ClientBuilder::new()
-        .use_rustls_tls()
-        .build()
-        .unwrap();
     let current_weather: Weather = client
-        .get(url)
-        .send()
-        .await?
-        .json::<WeatherBody>()
+        .get_json::<WeatherBody>(
+            Provider::OpenWeather,
+            &url,
+            &[("id", location_id.as_str()), ("appid", api_key)],
+        )
         .await?
         .into();
     Ok(current_weather)
 }
+
+/// Retrieve the hourly cloud cover forecast for the next 24 hours from the One
+/// Call API, which locates places by their coordinates.
+pub async fn get_cloud_cover_forecast(
+    client: &UpstreamClient,
+    location: &WeatherLocation,
+    api_key: &str,
+) -> Result<Vec<CloudCoverForecast>> {
+    let (latitude, longitude) = match (location.latitude, location.longitude) {
+        (Some(latitude), Some(longitude)) => (latitude.to_string(), longitude.to_string()),
+        _ => return Err(OpenWeatherError::MissingCoordinates(location.location_id)),
+    };
+    let url = format!("{}/onecall", client.settings.open_weather.one_call_base_url);
+
+    let forecast = client
+        .get_json::<ForecastBody>(
+            Provider::OpenWeather,
+            &url,
+            &[
+                ("lat", latitude.as_str()),
+                ("lon", longitude.as_str()),
+                ("exclude", "current,minutely,daily,alerts"),
+                ("appid", api_key),
+            ],
+        )
+        .await?
+        .into();
+    Ok(forecast)
+}
+
+/// OpenWeather, which locates places by its own city ids, which are also used
+/// as the ids of our locations, except for the forecast, which needs their
+/// coordinates.
+#[derive(Debug)]
+pub struct OpenWeatherProvider {
+    client: UpstreamClient,
+    api_key: String,
+}
+
+impl OpenWeatherProvider {
+    pub fn new(client: UpstreamClient, api_key: String) -> Self {
+        Self { client, api_key }
+    }
+}
+
+#[async_trait]
+impl WeatherProvider for OpenWeatherProvider {
+    fn name(&self) -> &'static str {
+        "OpenWeather"
+    }
+
+    async fn current_weather(
+        &self,
+        location: &WeatherLocation,
+    ) -> std::result::Result<Weather, WeatherError> {
+        Ok(get_weather(&self.client, location.location_id, &self.api_key).await?)
+    }
+
+    async fn cloud_cover_forecast(
+        &self,
+        location: &WeatherLocation,
+    ) -> std::result::Result<Vec<CloudCoverForecast>, WeatherError> {
+        Ok(get_cloud_cover_forecast(&self.client, location, &self.api_key).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weather_includes_coordinates() {
+        let body = r#"{
+            "coord": {"lon": -5.1052, "lat": 56.8198},
+            "weather": [{"id": 804, "main": "Clouds", "description": "overcast clouds"}],
+            "clouds": {"all": 100},
+            "id": 2649169,
+            "name": "Fort William"
+        }"#;
+
+        let weather: Weather = serde_json::from_str::<WeatherBody>(body).unwrap().into();
+
+        assert_eq!(weather.description, "overcast clouds");
+        assert_eq!(weather.cloud_cover, 100);
+        assert_eq!(weather.latitude, Some(56.8198));
+        assert_eq!(weather.longitude, Some(-5.1052));
+    }
+
+    #[test]
+    fn test_forecast_is_hourly_after_current_hour() {
+        let body = r#"{
+            "lat": 56.8198,
+            "lon": -5.1052,
+            "hourly": [
+                {"dt": 1665507600, "clouds": 100},
+                {"dt": 1665511200, "clouds": 80},
+                {"dt": 1665514800, "clouds": 20}
+            ]
+        }"#;
+
+        let forecast: Vec<CloudCoverForecast> =
+            serde_json::from_str::<ForecastBody>(body).unwrap().into();
+
+        assert_eq!(forecast.len(), 2);
+        assert_eq!(
+            forecast[0].timestamp,
+            chrono::Utc.ymd(2022, 10, 11).and_hms(18, 0, 0)
+        );
+        assert_eq!(forecast[0].cloud_cover, 80);
+        assert_eq!(
+            forecast[1].timestamp,
+            chrono::Utc.ymd(2022, 10, 11).and_hms(19, 0, 0)
+        );
+    }
+}
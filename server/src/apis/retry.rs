@@ -0,0 +1,284 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+
+use crate::configuration::RetrySettings;
+
+/// Each third party API, so that each gets its own circuit breaker.
+#[derive(Clone, Copy, Debug, derive_more::Display)]
+pub enum Provider {
+    #[display(fmt = "AuroraWatch UK")]
+    AuroraWatch,
+    #[display(fmt = "NOAA SWPC")]
+    NoaaSwpc,
+    #[display(fmt = "OpenWeather")]
+    OpenWeather,
+    #[display(fmt = "Open-Meteo")]
+    OpenMeteo,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum UpstreamError {
+    #[error("{0}")]
+    Request(reqwest::Error),
+    #[error("circuit breaker for {0} is open")]
+    CircuitOpen(Provider),
+}
+
+impl std::convert::From<reqwest::Error> for UpstreamError {
+    fn from(e: reqwest::Error) -> Self {
+        // Strip the URL, as it may contain an API key, and these errors are logged.
+        UpstreamError::Request(e.without_url())
+    }
+}
+
+/// How failed requests are retried.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    initial_backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(settings: &RetrySettings) -> Self {
+        Self {
+            max_attempts: settings.max_attempts.max(1),
+            initial_backoff: Duration::from_millis(settings.initial_backoff_milliseconds),
+            max_backoff: Duration::from_millis(settings.max_backoff_milliseconds),
+        }
+    }
+
+    /// The time to wait after the given (1-based) attempt has failed.
+    ///
+    /// The backoff grows exponentially up to a maximum, and the actual wait is
+    /// picked at random from within it, so that clients don't retry in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let ceiling = exponential.min(self.max_backoff).as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling))
+    }
+}
+
+/// Whether a failed request is worth retrying.
+pub fn is_transient(e: &reqwest::Error) -> bool {
+    if e.is_timeout() || e.is_connect() {
+        return true;
+    }
+    match e.status() {
+        Some(status) => status.is_server_error() || status.as_u16() == 429,
+        None => e.is_request(),
+    }
+}
+
+#[derive(Debug)]
+enum CircuitBreakerState {
+    /// Requests are let through, while counting how many in a row have failed.
+    Closed { consecutive_failures: u32 },
+    /// Requests fail fast until the cooldown is over.
+    Open { until: Instant },
+    /// The cooldown is over, and a single trial request has been let through to
+    /// see whether the provider has recovered.
+    HalfOpen { probe_started_at: Instant },
+}
+
+impl Default for CircuitBreakerState {
+    fn default() -> Self {
+        CircuitBreakerState::Closed {
+            consecutive_failures: 0,
+        }
+    }
+}
+
+/// Stops requests being made to a provider which keeps failing, until it has
+/// had some time to recover.
+///
+/// Clones share the same state.
+#[derive(Clone, Debug)]
+pub struct CircuitBreaker {
+    provider: Provider,
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Arc<Mutex<CircuitBreakerState>>,
+}
+
+impl CircuitBreaker {
+    pub fn new(provider: Provider, settings: &RetrySettings) -> Self {
+        Self {
+            provider,
+            failure_threshold: settings.circuit_breaker_failure_threshold.max(1),
+            cooldown: Duration::from_secs(settings.circuit_breaker_cooldown_seconds),
+            state: Arc::new(Mutex::new(CircuitBreakerState::default())),
+        }
+    }
+
+    /// Return an error if requests to the provider should not be attempted.
+    ///
+    /// Once the cooldown has passed, a single trial request is let through, and
+    /// all others keep failing fast until it completes. If the trial request
+    /// fails, the breaker opens again for another cooldown.
+    pub fn check(&self) -> Result<(), UpstreamError> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            CircuitBreakerState::Closed { .. } => Ok(()),
+            CircuitBreakerState::Open { until } if now < until => {
+                Err(UpstreamError::CircuitOpen(self.provider))
+            }
+            // A trial request which never reported back (e.g. because it was
+            // cancelled) is given up on after a cooldown, so that another can be made.
+            CircuitBreakerState::HalfOpen { probe_started_at }
+                if now < probe_started_at + self.cooldown =>
+            {
+                Err(UpstreamError::CircuitOpen(self.provider))
+            }
+            CircuitBreakerState::Open { .. } | CircuitBreakerState::HalfOpen { .. } => {
+                tracing::info!("letting a trial request through to {}", self.provider);
+                *state = CircuitBreakerState::HalfOpen {
+                    probe_started_at: now,
+                };
+                Ok(())
+            }
+        }
+    }
+
+    /// Record that the provider responded, closing the breaker if it was open.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        *state = CircuitBreakerState::Closed {
+            consecutive_failures: 0,
+        };
+    }
+
+    /// Record that a request to the provider failed in a way which suggests it is
+    /// unavailable.
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            CircuitBreakerState::Closed {
+                consecutive_failures,
+            } => {
+                let consecutive_failures = consecutive_failures + 1;
+                if consecutive_failures >= self.failure_threshold {
+                    tracing::warn!(
+                        "opening circuit breaker for {} for {:?} after {} consecutive failures",
+                        self.provider,
+                        self.cooldown,
+                        consecutive_failures
+                    );
+                    *state = CircuitBreakerState::Open {
+                        until: Instant::now() + self.cooldown,
+                    };
+                } else {
+                    *state = CircuitBreakerState::Closed {
+                        consecutive_failures,
+                    };
+                }
+            }
+            CircuitBreakerState::HalfOpen { .. } => {
+                tracing::warn!(
+                    "trial request to {} failed, reopening circuit breaker for {:?}",
+                    self.provider,
+                    self.cooldown
+                );
+                *state = CircuitBreakerState::Open {
+                    until: Instant::now() + self.cooldown,
+                };
+            }
+            // A request which was already in flight when the breaker opened.
+            CircuitBreakerState::Open { .. } => {}
+        }
+    }
+}
+
+/// The circuit breaker for every provider.
+#[derive(Clone, Debug)]
+pub struct CircuitBreakers {
+    aurora_watch: CircuitBreaker,
+    noaa_swpc: CircuitBreaker,
+    open_weather: CircuitBreaker,
+    open_meteo: CircuitBreaker,
+}
+
+impl CircuitBreakers {
+    pub fn new(settings: &RetrySettings) -> Self {
+        Self {
+            aurora_watch: CircuitBreaker::new(Provider::AuroraWatch, settings),
+            noaa_swpc: CircuitBreaker::new(Provider::NoaaSwpc, settings),
+            open_weather: CircuitBreaker::new(Provider::OpenWeather, settings),
+            open_meteo: CircuitBreaker::new(Provider::OpenMeteo, settings),
+        }
+    }
+
+    pub fn for_provider(&self, provider: Provider) -> &CircuitBreaker {
+        match provider {
+            Provider::AuroraWatch => &self.aurora_watch,
+            Provider::NoaaSwpc => &self.noaa_swpc,
+            Provider::OpenWeather => &self.open_weather,
+            Provider::OpenMeteo => &self.open_meteo,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> RetrySettings {
+        RetrySettings {
+            max_attempts: 3,
+            initial_backoff_milliseconds: 500,
+            max_backoff_milliseconds: 1500,
+            circuit_breaker_failure_threshold: 2,
+            circuit_breaker_cooldown_seconds: 300,
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::new(&settings());
+        for attempt in 1..10 {
+            assert!(policy.backoff(attempt) <= Duration::from_millis(1500));
+        }
+    }
+
+    #[test]
+    fn test_circuit_breaker_opens_after_threshold() {
+        let circuit_breaker = CircuitBreaker::new(Provider::NoaaSwpc, &settings());
+        circuit_breaker.record_failure();
+        assert!(circuit_breaker.check().is_ok());
+        circuit_breaker.record_failure();
+        assert!(circuit_breaker.check().is_err());
+        circuit_breaker.record_success();
+        assert!(circuit_breaker.check().is_ok());
+    }
+
+    fn open_circuit_breaker_after_cooldown() -> CircuitBreaker {
+        let circuit_breaker = CircuitBreaker::new(Provider::NoaaSwpc, &settings());
+        *circuit_breaker.state.lock().unwrap() = CircuitBreakerState::Open {
+            until: Instant::now(),
+        };
+        circuit_breaker
+    }
+
+    #[test]
+    fn test_circuit_breaker_admits_a_single_probe_after_cooldown() {
+        let circuit_breaker = open_circuit_breaker_after_cooldown();
+        assert!(circuit_breaker.check().is_ok());
+        assert!(circuit_breaker.check().is_err());
+        circuit_breaker.record_success();
+        assert!(circuit_breaker.check().is_ok());
+        assert!(circuit_breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_circuit_breaker_reopens_when_probe_fails() {
+        let circuit_breaker = open_circuit_breaker_after_cooldown();
+        assert!(circuit_breaker.check().is_ok());
+        circuit_breaker.record_failure();
+        assert!(circuit_breaker.check().is_err());
+    }
+}
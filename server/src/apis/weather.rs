@@ -0,0 +1,84 @@
+use async_trait::async_trait;
+
+use super::open_meteo::{OpenMeteoError, OpenMeteoProvider};
+use super::open_weather::{OpenWeatherError, OpenWeatherProvider};
+use super::UpstreamClient;
+use crate::configuration::{ApplicationSettings, WeatherProviderKind};
+use crate::types::DateTimeUtc;
+
+type Result<T> = std::result::Result<T, WeatherError>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum WeatherError {
+    #[error("OpenWeather: {0}")]
+    OpenWeather(#[from] OpenWeatherError),
+    #[error("Open-Meteo: {0}")]
+    OpenMeteo(#[from] OpenMeteoError),
+    #[error("no API key is configured for {0}")]
+    MissingApiKey(&'static str),
+}
+
+/// A location to fetch the weather for.
+///
+/// Providers identify locations differently, so this carries everything any of
+/// them may need.
+#[derive(Debug)]
+pub struct WeatherLocation {
+    /// The OpenWeather city id, which is also the primary key of `locations`.
+    pub location_id: i32,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// The current weather at a location.
+#[derive(Debug)]
+pub struct Weather {
+    pub description: String,
+    /// Cloud cover, as an integer percentage.
+    pub cloud_cover: i16,
+    /// Where the provider places the location, if it reports it. This is used to
+    /// fill in the coordinates of locations which don't have any yet.
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+/// The forecast cloud cover at a location for a single point in time.
+#[derive(Debug)]
+pub struct CloudCoverForecast {
+    pub timestamp: DateTimeUtc,
+    pub cloud_cover: i16,
+}
+
+/// A provider of current weather conditions and cloud cover forecasts.
+#[async_trait]
+pub trait WeatherProvider: std::fmt::Debug + Send + Sync {
+    /// A short, human readable name for the provider, used in logs.
+    fn name(&self) -> &'static str;
+
+    /// Retrieve the current weather.
+    async fn current_weather(&self, location: &WeatherLocation) -> Result<Weather>;
+
+    /// Retrieve the cloud cover forecast, typically for the next 24 hours.
+    async fn cloud_cover_forecast(
+        &self,
+        location: &WeatherLocation,
+    ) -> Result<Vec<CloudCoverForecast>>;
+}
+
+/// Build the weather provider selected in the configuration.
+pub fn from_settings(
+    settings: &ApplicationSettings,
+    client: &UpstreamClient,
+) -> Result<Box<dyn WeatherProvider>> {
+    match settings.weather_provider {
+        WeatherProviderKind::OpenWeather => {
+            let api_key = settings
+                .open_weather_api_key
+                .clone()
+                .filter(|api_key| !api_key.is_empty())
+                .ok_or(WeatherError::MissingApiKey("OpenWeather"))?;
+            Ok(Box::new(OpenWeatherProvider::new(client.clone(), api_key)))
+        }
+        WeatherProviderKind::OpenMeteo => Ok(Box::new(OpenMeteoProvider::new(client.clone()))),
+    }
+}
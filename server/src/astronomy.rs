@@ -0,0 +1,398 @@
+//! Low precision positions of the sun and moon, accurate to within a degree or
+//! so, which is plenty for deciding whether it is dark enough to see an aurora.
+//!
+//! Based on the formulae in Astronomical Algorithms by Jean Meeus, as used by
+//! the suncalc library.
+
+use std::f64::consts::PI;
+
+use chrono::TimeZone;
+use serde::{Serialize, Serializer};
+
+use crate::types::DateTimeUtc;
+
+const RAD: f64 = PI / 180.0;
+/// The obliquity of the Earth's axis.
+const OBLIQUITY: f64 = RAD * 23.4397;
+const J1970: f64 = 2_440_588.0;
+const J2000: f64 = 2_451_545.0;
+const J0: f64 = 0.0009;
+const SECONDS_PER_DAY: f64 = 60.0 * 60.0 * 24.0;
+/// The mean distance from the Earth to the sun, in km.
+const SUN_DISTANCE: f64 = 149_598_000.0;
+
+/// The altitude of the sun, in degrees, at each stage of twilight.
+const SUNRISE_ALTITUDE: f64 = -0.833;
+const CIVIL_TWILIGHT_ALTITUDE: f64 = -6.0;
+const NAUTICAL_TWILIGHT_ALTITUDE: f64 = -12.0;
+const ASTRONOMICAL_TWILIGHT_ALTITUDE: f64 = -18.0;
+
+fn to_julian(time: &DateTimeUtc) -> f64 {
+    time.timestamp() as f64 / SECONDS_PER_DAY - 0.5 + J1970
+}
+
+fn from_julian(julian: f64) -> Option<DateTimeUtc> {
+    if !julian.is_finite() {
+        return None;
+    }
+    let seconds = ((julian + 0.5 - J1970) * SECONDS_PER_DAY).round() as i64;
+    chrono::Utc.timestamp_opt(seconds, 0).single()
+}
+
+/// Days since the J2000 epoch.
+fn to_days(time: &DateTimeUtc) -> f64 {
+    to_julian(time) - J2000
+}
+
+fn right_ascension(longitude: f64, latitude: f64) -> f64 {
+    (longitude.sin() * OBLIQUITY.cos() - latitude.tan() * OBLIQUITY.sin()).atan2(longitude.cos())
+}
+
+fn declination(longitude: f64, latitude: f64) -> f64 {
+    (latitude.sin() * OBLIQUITY.cos() + latitude.cos() * OBLIQUITY.sin() * longitude.sin()).asin()
+}
+
+fn altitude(hour_angle: f64, phi: f64, declination: f64) -> f64 {
+    (phi.sin() * declination.sin() + phi.cos() * declination.cos() * hour_angle.cos()).asin()
+}
+
+fn sidereal_time(days: f64, lw: f64) -> f64 {
+    RAD * (280.16 + 360.985_623_5 * days) - lw
+}
+
+fn solar_mean_anomaly(days: f64) -> f64 {
+    RAD * (357.5291 + 0.985_600_28 * days)
+}
+
+fn ecliptic_longitude(mean_anomaly: f64) -> f64 {
+    let center = RAD
+        * (1.9148 * mean_anomaly.sin()
+            + 0.02 * (2.0 * mean_anomaly).sin()
+            + 0.0003 * (3.0 * mean_anomaly).sin());
+    let perihelion = RAD * 102.9372;
+    mean_anomaly + center + perihelion + PI
+}
+
+/// Equatorial coordinates, in radians, plus the distance in km.
+struct Coordinates {
+    right_ascension: f64,
+    declination: f64,
+    distance: f64,
+}
+
+fn sun_coordinates(days: f64) -> Coordinates {
+    let longitude = ecliptic_longitude(solar_mean_anomaly(days));
+    Coordinates {
+        right_ascension: right_ascension(longitude, 0.0),
+        declination: declination(longitude, 0.0),
+        distance: SUN_DISTANCE,
+    }
+}
+
+fn moon_coordinates(days: f64) -> Coordinates {
+    let mean_longitude = RAD * (218.316 + 13.176_396 * days);
+    let mean_anomaly = RAD * (134.963 + 13.064_993 * days);
+    let mean_distance = RAD * (93.272 + 13.229_350 * days);
+
+    let longitude = mean_longitude + RAD * 6.289 * mean_anomaly.sin();
+    let latitude = RAD * 5.128 * mean_distance.sin();
+    Coordinates {
+        right_ascension: right_ascension(longitude, latitude),
+        declination: declination(longitude, latitude),
+        distance: 385_001.0 - 20_905.0 * mean_anomaly.cos(),
+    }
+}
+
+/// The altitude, in degrees, of a body above the horizon at the given position.
+fn altitude_of(coordinates: &Coordinates, days: f64, latitude: f64, longitude: f64) -> f64 {
+    let lw = RAD * -longitude;
+    let phi = RAD * latitude;
+    let hour_angle = sidereal_time(days, lw) - coordinates.right_ascension;
+    altitude(hour_angle, phi, coordinates.declination) / RAD
+}
+
+/// The altitude of the sun, in degrees, at the given time and position.
+pub fn sun_altitude(time: &DateTimeUtc, latitude: f64, longitude: f64) -> f64 {
+    let days = to_days(time);
+    altitude_of(&sun_coordinates(days), days, latitude, longitude)
+}
+
+/// The altitude of the moon, in degrees, at the given time and position.
+pub fn moon_altitude(time: &DateTimeUtc, latitude: f64, longitude: f64) -> f64 {
+    let days = to_days(time);
+    altitude_of(&moon_coordinates(days), days, latitude, longitude)
+}
+
+/// How dark the sky is, going by the altitude of the sun.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, derive_more::Display)]
+pub enum Darkness {
+    #[display(fmt = "daylight")]
+    Day,
+    #[display(fmt = "civil twilight")]
+    CivilTwilight,
+    #[display(fmt = "nautical twilight")]
+    NauticalTwilight,
+    #[display(fmt = "astronomical twilight")]
+    AstronomicalTwilight,
+    #[display(fmt = "night")]
+    Night,
+}
+
+impl Darkness {
+    pub fn from_sun_altitude(altitude: f64) -> Self {
+        if altitude > SUNRISE_ALTITUDE {
+            Darkness::Day
+        } else if altitude > CIVIL_TWILIGHT_ALTITUDE {
+            Darkness::CivilTwilight
+        } else if altitude > NAUTICAL_TWILIGHT_ALTITUDE {
+            Darkness::NauticalTwilight
+        } else if altitude > ASTRONOMICAL_TWILIGHT_ALTITUDE {
+            Darkness::AstronomicalTwilight
+        } else {
+            Darkness::Night
+        }
+    }
+
+    pub fn at(time: &DateTimeUtc, latitude: f64, longitude: f64) -> Self {
+        Self::from_sun_altitude(sun_altitude(time, latitude, longitude))
+    }
+
+    /// Whether the sky is dark enough for an aurora to be seen.
+    ///
+    /// Bright aurorae are visible from the end of civil twilight, when the sun is
+    /// 6 degrees below the horizon.
+    pub fn is_dark(&self) -> bool {
+        *self >= Darkness::NauticalTwilight
+    }
+}
+
+impl Serialize for Darkness {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The state of the sky at a location, as included in alert emails.
+#[derive(Debug, Serialize)]
+pub struct Sky {
+    pub darkness: Darkness,
+    /// When it next stops being dark, if it is dark now.
+    pub dark_until: Option<DateTimeUtc>,
+    /// The altitude of the moon, in whole degrees.
+    pub moon_altitude: i16,
+}
+
+impl Sky {
+    pub fn at(time: &DateTimeUtc, latitude: f64, longitude: f64) -> Self {
+        let darkness = Darkness::at(time, latitude, longitude);
+        let dark_until = if darkness.is_dark() {
+            next_civil_dawn(time, latitude, longitude)
+        } else {
+            None
+        };
+
+        Self {
+            darkness,
+            dark_until,
+            moon_altitude: moon_altitude(time, latitude, longitude).round() as i16,
+        }
+    }
+}
+
+/// The time at which the sun crosses each altitude, on a given day.
+///
+/// Each event is `None` if the sun never crosses the altitude that day, e.g.
+/// there is no astronomical night in northern Scotland around midsummer.
+#[derive(Debug, Serialize)]
+pub struct SunTimes {
+    pub solar_noon: DateTimeUtc,
+    pub sunrise: Option<DateTimeUtc>,
+    pub sunset: Option<DateTimeUtc>,
+    pub civil_dawn: Option<DateTimeUtc>,
+    pub civil_dusk: Option<DateTimeUtc>,
+    pub nautical_dawn: Option<DateTimeUtc>,
+    pub nautical_dusk: Option<DateTimeUtc>,
+    pub astronomical_dawn: Option<DateTimeUtc>,
+    pub astronomical_dusk: Option<DateTimeUtc>,
+}
+
+fn julian_cycle(days: f64, lw: f64) -> f64 {
+    (days - J0 - lw / (2.0 * PI)).round()
+}
+
+fn approximate_transit(hour_angle: f64, lw: f64, cycle: f64) -> f64 {
+    J0 + (hour_angle + lw) / (2.0 * PI) + cycle
+}
+
+fn solar_transit_julian(days: f64, mean_anomaly: f64, longitude: f64) -> f64 {
+    J2000 + days + 0.0053 * mean_anomaly.sin() - 0.0069 * (2.0 * longitude).sin()
+}
+
+/// The hour angle at which the sun reaches `altitude`, which is NaN if it never
+/// does.
+fn hour_angle(altitude: f64, phi: f64, declination: f64) -> f64 {
+    ((altitude.sin() - phi.sin() * declination.sin()) / (phi.cos() * declination.cos())).acos()
+}
+
+/// Calculate the sun's rise, set and twilight times, for the day around `time`.
+pub fn sun_times(time: &DateTimeUtc, latitude: f64, longitude: f64) -> Option<SunTimes> {
+    let lw = RAD * -longitude;
+    let phi = RAD * latitude;
+
+    let days = to_days(time);
+    let cycle = julian_cycle(days, lw);
+    let transit = approximate_transit(0.0, lw, cycle);
+    let mean_anomaly = solar_mean_anomaly(transit);
+    let ecliptic = ecliptic_longitude(mean_anomaly);
+    let sun_declination = declination(ecliptic, 0.0);
+    let noon = solar_transit_julian(transit, mean_anomaly, ecliptic);
+
+    // Returns the (rise, set) times for the given altitude.
+    let events = |altitude: f64| {
+        let angle = hour_angle(RAD * altitude, phi, sun_declination);
+        let set = solar_transit_julian(
+            approximate_transit(angle, lw, cycle),
+            mean_anomaly,
+            ecliptic,
+        );
+        let rise = noon - (set - noon);
+        (from_julian(rise), from_julian(set))
+    };
+
+    let (sunrise, sunset) = events(SUNRISE_ALTITUDE);
+    let (civil_dawn, civil_dusk) = events(CIVIL_TWILIGHT_ALTITUDE);
+    let (nautical_dawn, nautical_dusk) = events(NAUTICAL_TWILIGHT_ALTITUDE);
+    let (astronomical_dawn, astronomical_dusk) = events(ASTRONOMICAL_TWILIGHT_ALTITUDE);
+
+    Some(SunTimes {
+        solar_noon: from_julian(noon)?,
+        sunrise,
+        sunset,
+        civil_dawn,
+        civil_dusk,
+        nautical_dawn,
+        nautical_dusk,
+        astronomical_dawn,
+        astronomical_dusk,
+    })
+}
+
+/// The first civil dawn after `time`, if there is one within the next day.
+pub fn next_civil_dawn(time: &DateTimeUtc, latitude: f64, longitude: f64) -> Option<DateTimeUtc> {
+    // The sun times are for the solar day nearest to the given time, so the dawn
+    // may have already passed.
+    [*time, *time + chrono::Duration::days(1)]
+        .iter()
+        .filter_map(|day| sun_times(day, latitude, longitude)?.civil_dawn)
+        .find(|dawn| dawn > time)
+}
+
+/// How much of the moon is lit, and where it is in its cycle.
+#[derive(Debug, Serialize)]
+pub struct MoonIllumination {
+    /// The illuminated fraction, from 0.0 (new moon) to 1.0 (full moon).
+    pub fraction: f64,
+    /// The position in the lunar cycle, from 0.0 (new moon), through 0.5 (full
+    /// moon), back round to 1.0.
+    pub phase: f64,
+}
+
+impl MoonIllumination {
+    pub fn at(time: &DateTimeUtc) -> Self {
+        let days = to_days(time);
+        let sun = sun_coordinates(days);
+        let moon = moon_coordinates(days);
+
+        let elongation = (sun.declination.sin() * moon.declination.sin()
+            + sun.declination.cos()
+                * moon.declination.cos()
+                * (sun.right_ascension - moon.right_ascension).cos())
+        .acos();
+        let inclination = (sun.distance * elongation.sin())
+            .atan2(moon.distance - sun.distance * elongation.cos());
+        let angle = (sun.declination.cos() * (sun.right_ascension - moon.right_ascension).sin())
+            .atan2(
+                sun.declination.sin() * moon.declination.cos()
+                    - sun.declination.cos()
+                        * moon.declination.sin()
+                        * (sun.right_ascension - moon.right_ascension).cos(),
+            );
+
+        Self {
+            fraction: (1.0 + inclination.cos()) / 2.0,
+            phase: 0.5 + 0.5 * inclination * angle.signum() / PI,
+        }
+    }
+
+    /// The illuminated fraction as a whole percentage.
+    pub fn percentage(&self) -> u8 {
+        (self.fraction * 100.0).round() as u8
+    }
+
+    /// The conventional name of the moon's phase.
+    pub fn phase_name(&self) -> &'static str {
+        match self.phase {
+            phase if !(0.03..0.97).contains(&phase) => "new moon",
+            phase if phase < 0.22 => "waxing crescent",
+            phase if phase < 0.28 => "first quarter",
+            phase if phase < 0.47 => "waxing gibbous",
+            phase if phase < 0.53 => "full moon",
+            phase if phase < 0.72 => "waning gibbous",
+            phase if phase < 0.78 => "last quarter",
+            _ => "waning crescent",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Edinburgh
+    const LATITUDE: f64 = 55.95;
+    const LONGITUDE: f64 = -3.19;
+
+    #[test]
+    fn test_darkness() {
+        let midday = chrono::Utc.ymd(2022, 12, 21).and_hms(12, 15, 0);
+        let midnight = chrono::Utc.ymd(2022, 12, 21).and_hms(23, 0, 0);
+
+        assert_eq!(Darkness::at(&midday, LATITUDE, LONGITUDE), Darkness::Day);
+        assert_eq!(
+            Darkness::at(&midnight, LATITUDE, LONGITUDE),
+            Darkness::Night
+        );
+        assert!(!Darkness::at(&midday, LATITUDE, LONGITUDE).is_dark());
+        assert!(Darkness::at(&midnight, LATITUDE, LONGITUDE).is_dark());
+    }
+
+    #[test]
+    fn test_sun_times_without_astronomical_night() {
+        let midsummer = chrono::Utc.ymd(2022, 6, 21).and_hms(12, 0, 0);
+
+        let times = sun_times(&midsummer, LATITUDE, LONGITUDE).unwrap();
+
+        // Sunrise in Edinburgh is at around 03:26 UTC on midsummer's day.
+        let sunrise = times.sunrise.unwrap();
+        assert_eq!(sunrise.date(), midsummer.date());
+        assert!(
+            (sunrise - chrono::Utc.ymd(2022, 6, 21).and_hms(3, 26, 0))
+                .num_minutes()
+                .abs()
+                < 5
+        );
+        assert!(times.astronomical_dawn.is_none());
+        assert!(times.astronomical_dusk.is_none());
+    }
+
+    #[test]
+    fn test_moon_illumination() {
+        // There was a full moon at 20:55 UTC on 9th October 2022.
+        let full_moon = chrono::Utc.ymd(2022, 10, 9).and_hms(20, 55, 0);
+
+        let illumination = MoonIllumination::at(&full_moon);
+
+        assert!(illumination.fraction > 0.99);
+        assert_eq!(illumination.phase_name(), "full moon");
+    }
+}
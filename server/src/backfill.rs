@@ -0,0 +1,66 @@
+use chrono::NaiveDate;
+
+use crate::apis::{aurora_watch, UpstreamClient};
+use crate::configuration::Settings;
+use crate::db;
+use crate::startup::{get_connection_pool, get_upstream_client};
+
+/// A pause between consecutive requests, so as not to hammer the archive.
+const REQUEST_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The outcome of a backfill run.
+#[derive(Debug, Default)]
+pub struct BackfillSummary {
+    pub days_stored: u32,
+    pub days_failed: u32,
+}
+
+/// Fetch and store the archived AuroraWatch UK activity data for every day from
+/// `start` to `end`, inclusive.
+///
+/// Existing data points are overwritten, so it is safe to backfill overlapping
+/// ranges more than once. Days which fail to download or parse are logged and
+/// skipped, rather than aborting the whole run.
+pub async fn backfill_activity_data(
+    config: Settings,
+    start: NaiveDate,
+    end: NaiveDate,
+) -> Result<BackfillSummary, anyhow::Error> {
+    if start > end {
+        anyhow::bail!("start date {start} is after end date {end}");
+    }
+
+    let pool = get_connection_pool(&config.database);
+    let upstream_client = get_upstream_client(&config.upstream)?;
+
+    let mut summary = BackfillSummary::default();
+    let mut date = start;
+    while date <= end {
+        match backfill_day(&upstream_client, date, &pool).await {
+            Ok(()) => {
+                tracing::info!("backfilled activity data for {date}");
+                summary.days_stored += 1;
+            }
+            Err(e) => {
+                tracing::error!("error backfilling activity data for {date}: {e}");
+                summary.days_failed += 1;
+            }
+        }
+
+        date = date.succ();
+        if date <= end {
+            tokio::time::sleep(REQUEST_INTERVAL).await;
+        }
+    }
+
+    Ok(summary)
+}
+
+async fn backfill_day(
+    client: &UpstreamClient,
+    date: NaiveDate,
+    pool: &db::DbPool,
+) -> Result<(), anyhow::Error> {
+    let activity = aurora_watch::get_archived_activity_data(client, date).await?;
+    db::update_aurora_activity(activity, pool).await
+}
@@ -0,0 +1,32 @@
+use chrono::NaiveDate;
+use server::{
+    backfill::backfill_activity_data, configuration::get_configuration, telemetry::init_tracing,
+};
+
+const USAGE: &str = "usage: backfill <start YYYY-MM-DD> <end YYYY-MM-DD>";
+
+/// Backfill the stored activity data from the AuroraWatch UK archive.
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (start, end) = match (args.next(), args.next()) {
+        (Some(start), Some(end)) => (
+            NaiveDate::parse_from_str(&start, "%F")?,
+            NaiveDate::parse_from_str(&end, "%F")?,
+        ),
+        _ => anyhow::bail!(USAGE),
+    };
+
+    let config = get_configuration()?;
+    init_tracing("info");
+
+    let summary = backfill_activity_data(config, start, end).await?;
+
+    tracing::info!(
+        "backfill complete: {} day(s) stored, {} day(s) failed",
+        summary.days_stored,
+        summary.days_failed
+    );
+
+    Ok(())
+}
@@ -29,13 +29,31 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub database: DatabaseSettings,
     pub email: EmailSettings,
+    pub tokens: TokenSettings,
+    pub upstream: UpstreamSettings,
+    pub verification: VerificationSettings,
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct ApplicationSettings {
     pub host: String,
     pub port: u16,
-    pub open_weather_api_key: String,
+    /// The public URL the frontend is served from, used for links in emails.
+    pub base_url: String,
+    pub weather_provider: WeatherProviderKind,
+    /// Only required when `weather_provider` is OpenWeather.
+    #[serde(default)]
+    pub open_weather_api_key: Option<String>,
+    /// The bearer token for the admin API, which is disabled if unset.
+    #[serde(default)]
+    pub admin_token: Option<String>,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum WeatherProviderKind {
+    OpenWeather,
+    OpenMeteo,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -49,8 +67,171 @@ pub struct DatabaseSettings {
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailSettings {
-    pub username: String,
-    pub password: String,
+    /// The mailbox emails are sent from, e.g. "Aurora Alert <alerts@example.com>".
+    pub sender: String,
+    pub transport: EmailTransportSettings,
+    pub outbox: OutboxSettings,
+}
+
+/// Where emails are delivered to.
+#[derive(serde::Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EmailTransportSettings {
+    Smtp {
+        host: String,
+        port: u16,
+        tls: SmtpTlsMode,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password: Option<String>,
+    },
+    /// Write each email to an `.eml` file in `directory`.
+    File { directory: std::path::PathBuf },
+    /// Keep emails in memory, for tests.
+    Memory,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpTlsMode {
+    /// Connect over TLS from the start, typically on port 465.
+    Wrapper,
+    /// Upgrade a plain connection with STARTTLS, typically on port 587.
+    Starttls,
+    /// Never use TLS, e.g. for a local mail catcher.
+    None,
+}
+
+/// How emails waiting in the outbox are delivered.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct OutboxSettings {
+    pub poll_interval_seconds: u64,
+    /// The maximum number of emails delivered per poll.
+    pub batch_size: i64,
+    /// The number of attempts after which an email is dead-lettered.
+    pub max_attempts: i32,
+    pub initial_backoff_seconds: i64,
+    pub max_backoff_seconds: i64,
+}
+
+impl OutboxSettings {
+    /// The time to wait before retrying an email which has failed `attempts`
+    /// times, doubling each time up to a maximum.
+    pub fn backoff(&self, attempts: i32) -> chrono::Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 30) as u32;
+        let seconds = self
+            .initial_backoff_seconds
+            .saturating_mul(2i64.saturating_pow(exponent))
+            .min(self.max_backoff_seconds);
+        chrono::Duration::seconds(seconds)
+    }
+
+    /// Whether an email which has failed `attempts` times should be given up on.
+    pub fn is_dead_letter(&self, attempts: i32) -> bool {
+        attempts >= self.max_attempts
+    }
+}
+
+/// How the tokens in links sent to users are signed, and how long they last.
+#[derive(serde::Deserialize, Clone)]
+pub struct TokenSettings {
+    /// The HMAC key, which must be at least 32 bytes long.
+    pub secret: String,
+    pub verify_ttl_hours: i64,
+    pub unsubscribe_ttl_hours: i64,
+    pub manage_ttl_hours: i64,
+}
+
+/// How long new users have to verify their email address.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct VerificationSettings {
+    /// Unverified users are deleted this long after registering.
+    pub window_hours: i64,
+    /// Unverified users are reminded this long before they're deleted.
+    pub reminder_hours: i64,
+    pub poll_interval_seconds: u64,
+}
+
+impl VerificationSettings {
+    pub fn window(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.window_hours)
+    }
+
+    pub fn reminder(&self) -> chrono::Duration {
+        chrono::Duration::hours(self.reminder_hours)
+    }
+}
+
+/// Settings shared by all of the clients which talk to third party APIs.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct UpstreamSettings {
+    pub user_agent: String,
+    pub timeout_milliseconds: u64,
+    pub retry: RetrySettings,
+    /// The geomagnetic data sources to use, in order of preference.
+    pub geomagnetic_sources: Vec<GeomagneticSourceKind>,
+    pub aurora_watch: AuroraWatchSettings,
+    pub noaa_swpc: NoaaSwpcSettings,
+    pub open_weather: OpenWeatherSettings,
+    pub open_meteo: OpenMeteoSettings,
+}
+
+impl UpstreamSettings {
+    pub fn timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+/// How failed requests to third party APIs are retried.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RetrySettings {
+    /// The maximum number of attempts per request, including the first.
+    pub max_attempts: u32,
+    pub initial_backoff_milliseconds: u64,
+    pub max_backoff_milliseconds: u64,
+    /// The number of consecutive failed requests before a provider is skipped.
+    pub circuit_breaker_failure_threshold: u32,
+    /// How long a provider is skipped for once its circuit breaker has opened.
+    pub circuit_breaker_cooldown_seconds: u64,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum GeomagneticSourceKind {
+    AuroraWatch,
+    NoaaSwpc,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct AuroraWatchSettings {
+    /// Base URL of the API serving `activity.txt`.
+    pub activity_base_url: String,
+    /// Paths, relative to `activity_base_url`, of the activity documents to
+    /// ingest. Each document reports the activity for a single station.
+    pub activity_paths: Vec<String>,
+    /// `strftime` format of the path, relative to `activity_base_url`, of the
+    /// archived activity document for a single day.
+    pub archive_path_format: String,
+    /// Base URL of the API serving `status/current-status.xml`.
+    pub status_base_url: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct NoaaSwpcSettings {
+    pub base_url: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct OpenWeatherSettings {
+    pub base_url: String,
+    /// The base URL of the One Call API, which provides the hourly forecast.
+    pub one_call_base_url: String,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct OpenMeteoSettings {
+    pub base_url: String,
 }
 
 /// The possible runtime environment for our application.
@@ -82,3 +263,39 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outbox_settings() -> OutboxSettings {
+        OutboxSettings {
+            poll_interval_seconds: 30,
+            batch_size: 10,
+            max_attempts: 5,
+            initial_backoff_seconds: 60,
+            max_backoff_seconds: 600,
+        }
+    }
+
+    #[test]
+    fn test_outbox_backoff_doubles_up_to_maximum() {
+        let settings = outbox_settings();
+        assert_eq!(settings.backoff(0), chrono::Duration::seconds(60));
+        assert_eq!(settings.backoff(1), chrono::Duration::seconds(60));
+        assert_eq!(settings.backoff(2), chrono::Duration::seconds(120));
+        assert_eq!(settings.backoff(3), chrono::Duration::seconds(240));
+        assert_eq!(settings.backoff(4), chrono::Duration::seconds(480));
+        assert_eq!(settings.backoff(5), chrono::Duration::seconds(600));
+        assert_eq!(settings.backoff(100), chrono::Duration::seconds(600));
+    }
+
+    #[test]
+    fn test_outbox_dead_letters_after_max_attempts() {
+        let settings = outbox_settings();
+        assert!(!settings.is_dead_letter(1));
+        assert!(!settings.is_dead_letter(4));
+        assert!(settings.is_dead_letter(5));
+        assert!(settings.is_dead_letter(6));
+    }
+}
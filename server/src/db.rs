@@ -1,19 +1,27 @@
 use std::collections::HashMap;
 
+use chrono::NaiveTime;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use sqlx::types::Uuid;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 
 use crate::apis;
-use crate::common::{ActivityData, ActivityDataPoint, AlertLevel};
+use crate::astronomy::MoonIllumination;
+use crate::astronomy::Sky;
+use crate::common::{
+    ActivityData, ActivityDataPoint, Aggregation, AlertCriterion, AlertLevel, Thresholds,
+};
+use crate::helpers::QuietHours;
 use crate::types::DateTimeUtc;
 use crate::types::SanitisedString;
+use crate::visibility::visibility_score;
 
 pub type DbPool = PgPool;
 
 /// Given an email address, return the associated user information.
 pub async fn get_user_by_email(
-    user: &RegisterUser,
+    email: &str,
     db: &DbPool,
 ) -> Result<Option<UserWithLocations>, anyhow::Error> {
     let user_locations = sqlx::query_as!(
@@ -22,13 +30,27 @@ pub async fn get_user_by_email(
             SELECT
               users.user_id,
               users.email::TEXT as "email!",
-              users.alert_threshold as "alert_threshold: AlertLevel",
               users.last_alerted_at as "last_alerted_at: DateTimeUtc",
+              users.alert_criterion as "alert_criterion: AlertCriterion",
+              users.min_visibility_score,
+              users.max_cloud_cover,
+              users.timezone,
+              users.quiet_hours_start,
+              users.quiet_hours_end,
+              users.alert_cooldown_minutes,
+              users.last_notified_level as "last_notified_level: AlertLevel",
+              users.notify_all_clear,
+              users.verified,
+              users.registered_at as "registered_at: DateTimeUtc",
               locations.location_id,
               locations.name::TEXT as "name!",
               locations.weather_description as "weather_description!",
               locations.cloud_cover,
-              locations.updated_at as "updated_at: DateTimeUtc"
+              locations.updated_at as "updated_at: DateTimeUtc",
+              locations.latitude,
+              locations.longitude,
+              user_locations.alert_threshold as "alert_threshold: AlertLevel",
+              user_locations.max_cloud_cover as location_max_cloud_cover
             FROM 
               users
             JOIN user_locations USING (user_id)
@@ -36,7 +58,61 @@ pub async fn get_user_by_email(
             WHERE
               users.email = $1::TEXT::CITEXT
         "#,
-        user.email
+        email
+    )
+    .fetch_all(db)
+    .await?;
+
+    if !user_locations.is_empty() {
+        Ok(Some(UserWithLocations::one_from_rows(user_locations)))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Given a user id, return the associated user information.
+pub async fn get_user<'c, E>(
+    user_id: &Uuid,
+    db: E,
+) -> Result<Option<UserWithLocations>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let user_locations = sqlx::query_as!(
+        UserWithLocationModel,
+        r#"
+            SELECT
+              users.user_id,
+              users.email::TEXT as "email!",
+              users.last_alerted_at as "last_alerted_at: DateTimeUtc",
+              users.alert_criterion as "alert_criterion: AlertCriterion",
+              users.min_visibility_score,
+              users.max_cloud_cover,
+              users.timezone,
+              users.quiet_hours_start,
+              users.quiet_hours_end,
+              users.alert_cooldown_minutes,
+              users.last_notified_level as "last_notified_level: AlertLevel",
+              users.notify_all_clear,
+              users.verified,
+              users.registered_at as "registered_at: DateTimeUtc",
+              locations.location_id,
+              locations.name::TEXT as "name!",
+              locations.weather_description as "weather_description!",
+              locations.cloud_cover,
+              locations.updated_at as "updated_at: DateTimeUtc",
+              locations.latitude,
+              locations.longitude,
+              user_locations.alert_threshold as "alert_threshold: AlertLevel",
+              user_locations.max_cloud_cover as location_max_cloud_cover
+            FROM 
+              users
+            JOIN user_locations USING (user_id)
+            JOIN locations USING (location_id)
+            WHERE
+              users.user_id = $1
+        "#,
+        user_id
     )
     .fetch_all(db)
     .await?;
@@ -55,6 +131,100 @@ pub struct Location {
     pub weather_description: String,
     pub cloud_cover: i16,
     pub updated_at: DateTimeUtc,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub forecast: Vec<ForecastPoint>,
+    pub sky: Option<Sky>,
+    pub visibility_score: Option<i16>,
+    /// The user's alert threshold, if this is one of a user's locations.
+    pub alert_threshold: Option<AlertLevel>,
+    /// Overrides the user's maximum cloud cover for this location.
+    pub max_cloud_cover: Option<i16>,
+}
+
+impl Location {
+    /// Fill in the state of the sky at `time`, if the location has coordinates.
+    pub fn attach_sky(&mut self, time: &DateTimeUtc) {
+        if let (Some(latitude), Some(longitude)) = (self.latitude, self.longitude) {
+            self.sky = Some(Sky::at(time, latitude, longitude));
+        }
+    }
+
+    /// Score the visibility of an aurora at the location.
+    ///
+    /// The sky should be attached first, otherwise it is assumed to be dark.
+    pub fn attach_visibility_score(&mut self, alert_level: AlertLevel, moon: &MoonIllumination) {
+        self.visibility_score = Some(visibility_score(
+            alert_level,
+            self.cloud_cover,
+            self.sky.as_ref(),
+            moon,
+        ));
+    }
+}
+
+/// Of the given location ids, return those which don't exist.
+pub async fn get_unknown_location_ids(
+    location_ids: &[i32],
+    db: &DbPool,
+) -> Result<Vec<i32>, anyhow::Error> {
+    let unknown_location_ids = sqlx::query_scalar!(
+        r#"
+            SELECT
+              ids.location_id as "location_id!"
+            FROM
+              UNNEST($1::INT[]) AS ids (location_id)
+            WHERE
+              ids.location_id NOT IN (SELECT locations.location_id FROM locations)
+        "#,
+        location_ids
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(unknown_location_ids)
+}
+
+/// Retrieve a single location, including its latest weather report.
+pub async fn get_location(
+    location_id: i32,
+    db: &DbPool,
+) -> Result<Option<Location>, anyhow::Error> {
+    let location = sqlx::query!(
+        r#"
+            SELECT
+              location_id,
+              name::TEXT as "name!",
+              weather_description,
+              cloud_cover,
+              updated_at as "updated_at: DateTimeUtc",
+              latitude,
+              longitude
+            FROM
+              locations
+            WHERE
+              location_id = $1
+        "#,
+        location_id
+    )
+    .fetch_optional(db)
+    .await?
+    .map(|row| Location {
+        location_id: row.location_id,
+        name: row.name,
+        weather_description: row.weather_description,
+        cloud_cover: row.cloud_cover,
+        updated_at: row.updated_at,
+        latitude: row.latitude,
+        longitude: row.longitude,
+        forecast: vec![],
+        sky: None,
+        visibility_score: None,
+        alert_threshold: None,
+        max_cloud_cover: None,
+    });
+
+    Ok(location)
 }
 
 #[derive(Serialize)]
@@ -91,15 +261,11 @@ pub async fn get_locations(
     Ok(locations)
 }
 
-/// Mark the user with the given identifiers as verified.
+/// Mark the user with the given id as verified.
 ///
 /// The successful value is `Some(user_id)` if the user was found and updated,
 /// otherwise `None` not found.
-pub async fn set_user_verified(
-    user_id: &Uuid,
-    email: &str,
-    db: &DbPool,
-) -> Result<Option<Uuid>, anyhow::Error> {
+pub async fn set_user_verified(user_id: &Uuid, db: &DbPool) -> Result<Option<Uuid>, anyhow::Error> {
     let user_id = sqlx::query_scalar!(
         r#"
             UPDATE 
@@ -107,12 +273,11 @@ pub async fn set_user_verified(
             SET 
               verified = true
             WHERE 
-              user_id = $1 AND email = $2::TEXT::CITEXT
+              user_id = $1
             RETURNING 
               user_id
         "#,
-        user_id,
-        email
+        user_id
     )
     .fetch_optional(db)
     .await?;
@@ -120,26 +285,21 @@ pub async fn set_user_verified(
     Ok(user_id)
 }
 
-/// Permanently remove the user with the given identifiers from the database.
+/// Permanently remove the user with the given id from the database.
 ///
 /// The successful value is `Some(user_id)` if a user was found and deleted,
 /// otherwise it is `None`.
-pub async fn delete_user(
-    user_id: &Uuid,
-    email: &str,
-    db: &DbPool,
-) -> Result<Option<Uuid>, anyhow::Error> {
+pub async fn delete_user(user_id: &Uuid, db: &DbPool) -> Result<Option<Uuid>, anyhow::Error> {
     let user_id = sqlx::query_scalar!(
         "
             DELETE FROM 
               users
             WHERE 
-              user_id = $1 AND email = $2::TEXT::CITEXT
+              user_id = $1
             RETURNING 
               user_id
         ",
-        user_id,
-        email
+        user_id
     )
     .fetch_optional(db)
     .await?;
@@ -150,46 +310,88 @@ pub async fn delete_user(
 #[derive(Deserialize)]
 pub struct RegisterUser {
     pub email: String,
+    #[serde(default)]
+    pub alert_criterion: AlertCriterion,
+    #[serde(default = "default_min_visibility_score")]
+    pub min_visibility_score: i16,
+    #[serde(default = "default_max_cloud_cover")]
+    pub max_cloud_cover: i16,
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    #[serde(default = "default_alert_cooldown_minutes")]
+    pub alert_cooldown_minutes: i32,
+    #[serde(default)]
+    pub notify_all_clear: bool,
+    pub locations: Vec<RegisterLocation>,
+}
+
+/// A location that a new user is subscribing to, with their preferences for it.
+#[derive(Deserialize)]
+pub struct RegisterLocation {
+    pub location_id: i32,
     pub alert_threshold: AlertLevel,
-    pub locations: Vec<i32>,
+    pub max_cloud_cover: Option<i16>,
+}
+
+fn default_min_visibility_score() -> i16 {
+    50
+}
+
+fn default_max_cloud_cover() -> i16 {
+    100
+}
+
+fn default_timezone() -> Tz {
+    chrono_tz::Europe::London
+}
+
+fn default_alert_cooldown_minutes() -> i32 {
+    120
 }
 
 /// Create a new user record in the database, including associated locations.
+///
+/// The caller is responsible for committing the transaction, so that emails to
+/// the new user can be enqueued within it.
 pub async fn insert_user(
     user: &RegisterUser,
-    db: &DbPool,
+    tx: &mut Transaction<'_, Postgres>,
 ) -> Result<UserWithLocations, anyhow::Error> {
-    let mut tx = db.begin().await?;
-
     let user_id = sqlx::query_scalar!(
         r#"
             INSERT INTO users 
-              (email, alert_threshold)
+              (
+                email,
+                alert_criterion,
+                min_visibility_score,
+                max_cloud_cover,
+                timezone,
+                quiet_hours_start,
+                quiet_hours_end,
+                alert_cooldown_minutes,
+                notify_all_clear
+              )
             VALUES 
-              ($1::TEXT::CITEXT, $2)
+              ($1::TEXT::CITEXT, $2, $3, $4, $5, $6, $7, $8, $9)
             RETURNING
               user_id
         "#,
         user.email,
-        user.alert_threshold as AlertLevel
+        user.alert_criterion as AlertCriterion,
+        user.min_visibility_score,
+        user.max_cloud_cover,
+        user.timezone.name(),
+        user.quiet_hours_start,
+        user.quiet_hours_end,
+        user.alert_cooldown_minutes,
+        user.notify_all_clear
     )
-    .fetch_one(&mut tx)
+    .fetch_one(&mut *tx)
     .await?;
 
-    for location in &user.locations {
-        sqlx::query!(
-            "
-                INSERT INTO user_locations
-                  (user_id, location_id)
-                VALUES 
-                  ($1, $2)
-            ",
-            user_id,
-            location
-        )
-        .execute(&mut tx)
-        .await?;
-    }
+    insert_user_locations(&user_id, &user.locations, tx).await?;
 
     let user_locations = sqlx::query_as!(
         UserWithLocationModel,
@@ -197,13 +399,27 @@ pub async fn insert_user(
           SELECT
             users.user_id,
             users.email::TEXT as "email!",
-            users.alert_threshold as "alert_threshold: AlertLevel",
             users.last_alerted_at as "last_alerted_at: DateTimeUtc",
+            users.alert_criterion as "alert_criterion: AlertCriterion",
+            users.min_visibility_score,
+            users.max_cloud_cover,
+            users.timezone,
+            users.quiet_hours_start,
+            users.quiet_hours_end,
+            users.alert_cooldown_minutes,
+            users.last_notified_level as "last_notified_level: AlertLevel",
+            users.notify_all_clear,
+            users.verified,
+            users.registered_at as "registered_at: DateTimeUtc",
             locations.location_id,
             locations.name::TEXT as "name!",
             locations.weather_description as "weather_description!",
             locations.cloud_cover,
-            locations.updated_at as "updated_at: DateTimeUtc"
+            locations.updated_at as "updated_at: DateTimeUtc",
+            locations.latitude,
+            locations.longitude,
+            user_locations.alert_threshold as "alert_threshold: AlertLevel",
+            user_locations.max_cloud_cover as location_max_cloud_cover
           FROM 
             users
           JOIN user_locations USING (user_id)
@@ -213,20 +429,147 @@ pub async fn insert_user(
         "#,
         user_id
     )
-    .fetch_all(&mut tx)
+    .fetch_all(&mut *tx)
     .await?;
 
-    let user = UserWithLocations::one_from_rows(user_locations);
+    Ok(UserWithLocations::one_from_rows(user_locations))
+}
 
-    tx.commit().await?;
+async fn insert_user_locations(
+    user_id: &Uuid,
+    locations: &[RegisterLocation],
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), anyhow::Error> {
+    for location in locations {
+        sqlx::query!(
+            "
+                INSERT INTO user_locations
+                  (user_id, location_id, alert_threshold, max_cloud_cover)
+                VALUES 
+                  ($1, $2, $3, $4)
+            ",
+            user_id,
+            location.location_id,
+            location.alert_threshold as AlertLevel,
+            location.max_cloud_cover
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
 
-    Ok(user)
+    Ok(())
+}
+
+/// The preferences an existing user can change, other than their locations.
+#[derive(Deserialize)]
+pub struct UpdatePreferences {
+    pub alert_criterion: AlertCriterion,
+    pub min_visibility_score: i16,
+    pub max_cloud_cover: i16,
+    pub timezone: Tz,
+    pub quiet_hours_start: Option<NaiveTime>,
+    pub quiet_hours_end: Option<NaiveTime>,
+    pub alert_cooldown_minutes: i32,
+    pub notify_all_clear: bool,
+}
+
+/// Replace the preferences of the user with the given id.
+///
+/// The successful value is `Some(user_id)` if the user was found and updated,
+/// otherwise it is `None`.
+pub async fn update_user_preferences(
+    user_id: &Uuid,
+    preferences: &UpdatePreferences,
+    db: &DbPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let user_id = sqlx::query_scalar!(
+        r#"
+            UPDATE
+              users
+            SET
+              alert_criterion = $2,
+              min_visibility_score = $3,
+              max_cloud_cover = $4,
+              timezone = $5,
+              quiet_hours_start = $6,
+              quiet_hours_end = $7,
+              alert_cooldown_minutes = $8,
+              notify_all_clear = $9
+            WHERE
+              user_id = $1
+            RETURNING
+              user_id
+        "#,
+        user_id,
+        preferences.alert_criterion as AlertCriterion,
+        preferences.min_visibility_score,
+        preferences.max_cloud_cover,
+        preferences.timezone.name(),
+        preferences.quiet_hours_start,
+        preferences.quiet_hours_end,
+        preferences.alert_cooldown_minutes,
+        preferences.notify_all_clear
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(user_id)
+}
+
+/// Replace the locations, and the preferences for each, of the user with the
+/// given id.
+///
+/// The successful value is `Some(user_id)` if the user was found and updated,
+/// otherwise it is `None`. The caller is responsible for committing the
+/// transaction.
+pub async fn replace_user_locations(
+    user_id: &Uuid,
+    locations: &[RegisterLocation],
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    // lock the user, so that concurrent replacements can't interleave
+    let user_id = sqlx::query_scalar!(
+        "
+            SELECT
+              user_id
+            FROM
+              users
+            WHERE
+              user_id = $1
+            FOR UPDATE
+        ",
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(None),
+    };
+
+    sqlx::query!(
+        "
+            DELETE FROM
+              user_locations
+            WHERE
+              user_id = $1
+        ",
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    insert_user_locations(&user_id, locations, tx).await?;
+
+    Ok(Some(user_id))
 }
 
 #[derive(Debug)]
 pub struct AlertLevelModel {
     pub alert_level: AlertLevel,
     pub updated_at: DateTimeUtc,
+    pub site_id: String,
 }
 
 /// Retrieve the stored alert level from the database.
@@ -236,7 +579,8 @@ pub async fn get_alert_level(pool: &DbPool) -> Result<AlertLevelModel, anyhow::E
         r#"
             SELECT
               alert_level as "alert_level: AlertLevel",
-              updated_at as "updated_at: DateTimeUtc"
+              updated_at as "updated_at: DateTimeUtc",
+              site_id
             FROM
               alert_level
             WHERE
@@ -252,7 +596,7 @@ pub async fn get_alert_level(pool: &DbPool) -> Result<AlertLevelModel, anyhow::E
 /// Update the existing alert level stored in the database with a new alert
 /// level.
 pub async fn update_alert_level(
-    new_alert_level: &apis::aurora_watch::CurrentAlertLevel,
+    new_alert_level: &apis::geomagnetic::CurrentAlertLevel,
     pool: &DbPool,
 ) -> Result<(), anyhow::Error> {
     sqlx::query!(
@@ -261,12 +605,14 @@ pub async fn update_alert_level(
               alert_level
             SET
               alert_level = $1,
-              updated_at = $2
+              updated_at = $2,
+              site_id = $3
             WHERE
               alert_level_id = 1
         ",
         new_alert_level.level as AlertLevel,
-        new_alert_level.updated_at
+        new_alert_level.updated_at,
+        new_alert_level.site_id
     )
     .execute(pool)
     .await?;
@@ -278,6 +624,18 @@ pub async fn update_alert_level(
 pub struct LocationUpdatedAt {
     pub location_id: i32,
     pub updated_at: DateTimeUtc,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl LocationUpdatedAt {
+    pub fn weather_location(&self) -> apis::weather::WeatherLocation {
+        apis::weather::WeatherLocation {
+            location_id: self.location_id,
+            latitude: self.latitude,
+            longitude: self.longitude,
+        }
+    }
 }
 
 /// Retrieve a set of all locations which are associated with at least one user.
@@ -289,7 +647,9 @@ pub async fn get_unique_user_locations(
         r#"
             SELECT DISTINCT
               locations.location_id,
-              locations.updated_at as "updated_at: DateTimeUtc"
+              locations.updated_at as "updated_at: DateTimeUtc",
+              locations.latitude,
+              locations.longitude
             FROM
               user_locations INNER JOIN locations USING (location_id)
         "#
@@ -301,8 +661,11 @@ pub async fn get_unique_user_locations(
 }
 
 /// Update the weather report at the given location.
+///
+/// If the report includes the location's coordinates, those are stored too, so
+/// that locations added without coordinates are filled in.
 pub async fn update_weather(
-    weather: apis::open_weather::Weather,
+    weather: apis::weather::Weather,
     location_id: i32,
     pool: &DbPool,
 ) -> Result<(), anyhow::Error> {
@@ -312,12 +675,16 @@ pub async fn update_weather(
               locations
             SET
               weather_description = $1,
-              cloud_cover = $2
+              cloud_cover = $2,
+              latitude = COALESCE($3, latitude),
+              longitude = COALESCE($4, longitude)
             WHERE
-              location_id = $3
+              location_id = $5
         ",
         weather.description,
         weather.cloud_cover,
+        weather.latitude,
+        weather.longitude,
         location_id
     )
     .execute(pool)
@@ -326,24 +693,519 @@ pub async fn update_weather(
     Ok(())
 }
 
-/// Set the `last_updated_at` field for the given user to now.
-pub async fn update_user_last_alerted_at(
-    user_id: &Uuid,
+/// Replace the stored cloud cover forecast for the given location.
+///
+/// Forecasts for times which have already passed are removed.
+pub async fn update_cloud_cover_forecast(
+    forecast: &[apis::weather::CloudCoverForecast],
+    location_id: i32,
     pool: &DbPool,
 ) -> Result<(), anyhow::Error> {
+    let mut tx = pool.begin().await?;
+    let now = chrono::Utc::now();
+
+    sqlx::query!(
+        "
+            DELETE FROM
+              cloud_cover_forecasts
+            WHERE
+              location_id = $1 AND timestamp < $2
+        ",
+        location_id,
+        now
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let timestamps = forecast
+        .iter()
+        .map(|point| point.timestamp)
+        .collect::<Vec<_>>();
+    let cloud_covers = forecast
+        .iter()
+        .map(|point| point.cloud_cover)
+        .collect::<Vec<_>>();
+
+    sqlx::query!(
+        "
+            INSERT INTO cloud_cover_forecasts
+              (location_id, timestamp, cloud_cover, updated_at)
+            SELECT
+              $1::INTEGER, forecast.timestamp, forecast.cloud_cover, $4
+            FROM
+              UNNEST($2::TIMESTAMPTZ[], $3::SMALLINT[]) as forecast(timestamp, cloud_cover)
+            ON CONFLICT (location_id, timestamp) DO UPDATE
+              SET cloud_cover = EXCLUDED.cloud_cover, updated_at = EXCLUDED.updated_at
+        ",
+        location_id,
+        &timestamps,
+        &cloud_covers,
+        now
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ForecastPoint {
+    pub timestamp: DateTimeUtc,
+    pub cloud_cover: i16,
+}
+
+struct ForecastPointModel {
+    location_id: i32,
+    timestamp: DateTimeUtc,
+    cloud_cover: i16,
+}
+
+/// Retrieve the cloud cover forecasts for the given locations from `from`
+/// onwards, keyed by location and sorted by time.
+pub async fn get_cloud_cover_forecasts(
+    location_ids: &[i32],
+    from: &DateTimeUtc,
+    pool: &DbPool,
+) -> Result<HashMap<i32, Vec<ForecastPoint>>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        ForecastPointModel,
+        r#"
+            SELECT
+              location_id,
+              timestamp as "timestamp: DateTimeUtc",
+              cloud_cover
+            FROM
+              cloud_cover_forecasts
+            WHERE
+              location_id = ANY($1) AND timestamp >= $2
+            ORDER BY
+              location_id ASC, timestamp ASC
+        "#,
+        location_ids,
+        from
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut forecasts: HashMap<i32, Vec<ForecastPoint>> = HashMap::new();
+    for row in rows {
+        forecasts
+            .entry(row.location_id)
+            .or_default()
+            .push(ForecastPoint {
+                timestamp: row.timestamp,
+                cloud_cover: row.cloud_cover,
+            });
+    }
+
+    Ok(forecasts)
+}
+
+/// Record that the given user has been alerted about `alert_level` just now.
+pub async fn update_user_last_notified<'c, E>(
+    user_id: &Uuid,
+    alert_level: AlertLevel,
+    db: E,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
     let now = chrono::Utc::now();
     sqlx::query!(
         "
             UPDATE
               users
             SET
-              last_alerted_at = $1
+              last_alerted_at = $1,
+              last_notified_level = $2
             WHERE 
-              user_id = $2
+              user_id = $3
         ",
         now,
+        alert_level as AlertLevel,
         user_id
     )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Lower the level the given user was last alerted about, once activity has
+/// dropped part of the way.
+pub async fn update_user_last_notified_level<'c, E>(
+    user_id: &Uuid,
+    alert_level: AlertLevel,
+    db: E,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    sqlx::query!(
+        "
+            UPDATE
+              users
+            SET
+              last_notified_level = $1
+            WHERE 
+              user_id = $2
+        ",
+        alert_level as AlertLevel,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Forget the level the given user was last alerted about, once activity has
+/// subsided.
+pub async fn clear_user_last_notified_level<'c, E>(
+    user_id: &Uuid,
+    db: E,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    sqlx::query!(
+        "
+            UPDATE
+              users
+            SET
+              last_notified_level = NULL
+            WHERE 
+              user_id = $1
+        ",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// What a notification was sent to tell a user.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "notification_kind_enum", rename_all = "snake_case")]
+pub enum NotificationKind {
+    Alert,
+    AllClear,
+}
+
+/// Whether a notification has been delivered to the mail server.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, sqlx::Type)]
+#[serde(rename_all = "snake_case")]
+#[sqlx(type_name = "notification_status_enum", rename_all = "snake_case")]
+pub enum NotificationStatus {
+    Pending,
+    Sent,
+    Failed,
+}
+
+/// Record that a notification is to be sent to the given user, returning its ID
+/// so that the email can be enqueued against it.
+pub async fn insert_notification<'c, E>(
+    user: &UserWithLocations,
+    kind: NotificationKind,
+    alert_level: &apis::geomagnetic::CurrentAlertLevel,
+    db: E,
+) -> Result<Uuid, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let notification_id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO notifications
+              (user_id, email, kind, alert_level, site_id)
+            VALUES
+              ($1, $2::TEXT::CITEXT, $3, $4, $5)
+            RETURNING
+              notification_id
+        "#,
+        user.user_id,
+        user.email,
+        kind as NotificationKind,
+        alert_level.level as AlertLevel,
+        alert_level.site_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(notification_id)
+}
+
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub notification_id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub email: String,
+    pub kind: NotificationKind,
+    pub alert_level: AlertLevel,
+    pub site_id: String,
+    pub status: NotificationStatus,
+    pub error: Option<String>,
+    pub created_at: DateTimeUtc,
+    pub sent_at: Option<DateTimeUtc>,
+}
+
+/// Which notifications to retrieve, where each filter is ignored if unset.
+#[derive(Debug, Deserialize)]
+pub struct NotificationFilter {
+    pub email: Option<String>,
+    pub user_id: Option<Uuid>,
+    pub kind: Option<NotificationKind>,
+    pub status: Option<NotificationStatus>,
+    pub since: Option<DateTimeUtc>,
+    pub until: Option<DateTimeUtc>,
+}
+
+/// Retrieve the most recent notifications which match the filter, newest first.
+pub async fn get_notifications(
+    filter: &NotificationFilter,
+    limit: i64,
+    pool: &DbPool,
+) -> Result<Vec<Notification>, anyhow::Error> {
+    let notifications = sqlx::query_as!(
+        Notification,
+        r#"
+            SELECT
+              notification_id,
+              user_id,
+              email::TEXT as "email!",
+              kind as "kind: NotificationKind",
+              alert_level as "alert_level: AlertLevel",
+              site_id,
+              status as "status: NotificationStatus",
+              error,
+              created_at as "created_at: DateTimeUtc",
+              sent_at as "sent_at: DateTimeUtc"
+            FROM
+              notifications
+            WHERE
+              ($1::TEXT IS NULL OR email = $1::TEXT::CITEXT)
+              AND ($2::UUID IS NULL OR user_id = $2)
+              AND ($3::notification_kind_enum IS NULL OR kind = $3)
+              AND ($4::notification_status_enum IS NULL OR status = $4)
+              AND ($5::TIMESTAMPTZ IS NULL OR created_at >= $5)
+              AND ($6::TIMESTAMPTZ IS NULL OR created_at < $6)
+            ORDER BY
+              created_at DESC
+            LIMIT
+              $7
+        "#,
+        filter.email,
+        filter.user_id,
+        filter.kind as Option<NotificationKind>,
+        filter.status as Option<NotificationStatus>,
+        filter.since,
+        filter.until,
+        limit
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(notifications)
+}
+
+/// Enqueue an email to be delivered by the outbox worker, returning its ID.
+pub async fn enqueue_email<'c, E>(
+    to_address: &str,
+    subject: &str,
+    body: &str,
+    text_body: &str,
+    list_unsubscribe: Option<&str>,
+    notification_id: Option<Uuid>,
+    db: E,
+) -> Result<Uuid, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let email_id = sqlx::query_scalar!(
+        "
+            INSERT INTO email_outbox
+              (notification_id, to_address, subject, body, text_body, list_unsubscribe)
+            VALUES
+              ($1, $2, $3, $4, $5, $6)
+            RETURNING
+              email_id
+        ",
+        notification_id,
+        to_address,
+        subject,
+        body,
+        text_body,
+        list_unsubscribe
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(email_id)
+}
+
+/// When an email which isn't tied to a notification, such as a verification
+/// email or a manage link, was last enqueued for the given address.
+pub async fn get_last_account_email_at(
+    to_address: &str,
+    db: &DbPool,
+) -> Result<Option<DateTimeUtc>, anyhow::Error> {
+    let created_at = sqlx::query_scalar!(
+        r#"
+            SELECT
+              MAX(created_at) as "created_at: DateTimeUtc"
+            FROM
+              email_outbox
+            WHERE
+              to_address = $1 AND notification_id IS NULL
+        "#,
+        to_address
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(created_at)
+}
+
+#[derive(Debug)]
+pub struct OutboxEmail {
+    pub email_id: Uuid,
+    pub to_address: String,
+    pub subject: String,
+    pub body: String,
+    pub text_body: Option<String>,
+    pub list_unsubscribe: Option<String>,
+    pub attempts: i32,
+}
+
+/// Claim up to `limit` emails which are due to be delivered.
+///
+/// Claimed emails aren't due again until `lease` has passed, so that they
+/// aren't delivered twice, but are retried if the worker dies part way through.
+pub async fn claim_outbox_emails(
+    limit: i64,
+    lease: chrono::Duration,
+    pool: &DbPool,
+) -> Result<Vec<OutboxEmail>, anyhow::Error> {
+    let now = chrono::Utc::now();
+    let emails = sqlx::query_as!(
+        OutboxEmail,
+        "
+            UPDATE
+              email_outbox
+            SET
+              next_attempt_at = $3
+            WHERE
+              email_id IN (
+                SELECT
+                  email_id
+                FROM
+                  email_outbox
+                WHERE
+                  status = 'pending' AND next_attempt_at <= $1
+                ORDER BY
+                  next_attempt_at ASC
+                LIMIT
+                  $2
+                FOR UPDATE SKIP LOCKED
+              )
+            RETURNING
+              email_id,
+              to_address,
+              subject,
+              body,
+              text_body,
+              list_unsubscribe,
+              attempts
+        ",
+        now,
+        limit,
+        now + lease
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(emails)
+}
+
+/// Record that an email has been delivered, along with its notification.
+pub async fn mark_email_sent(email_id: &Uuid, pool: &DbPool) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "
+            WITH sent AS (
+              UPDATE
+                email_outbox
+              SET
+                status = 'sent',
+                attempts = attempts + 1,
+                last_error = NULL,
+                sent_at = CURRENT_TIMESTAMP
+              WHERE
+                email_id = $1
+              RETURNING
+                notification_id
+            )
+            UPDATE
+              notifications
+            SET
+              status = 'sent',
+              error = NULL,
+              sent_at = CURRENT_TIMESTAMP
+            FROM
+              sent
+            WHERE
+              notifications.notification_id = sent.notification_id
+        ",
+        email_id
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Record a failed attempt to deliver an email.
+///
+/// The email is retried at `retry_at`, or dead-lettered, and its notification
+/// marked as failed, if that is `None`.
+pub async fn record_email_failure(
+    email_id: &Uuid,
+    error: &str,
+    retry_at: Option<DateTimeUtc>,
+    pool: &DbPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        "
+            WITH failed AS (
+              UPDATE
+                email_outbox
+              SET
+                attempts = attempts + 1,
+                last_error = $2,
+                status = CASE WHEN $3::TIMESTAMPTZ IS NULL THEN 'dead' ELSE status END,
+                next_attempt_at = COALESCE($3, next_attempt_at)
+              WHERE
+                email_id = $1
+              RETURNING
+                notification_id,
+                status
+            )
+            UPDATE
+              notifications
+            SET
+              status = CASE WHEN failed.status = 'dead' THEN 'failed' ELSE notifications.status END,
+              error = $2
+            FROM
+              failed
+            WHERE
+              notifications.notification_id = failed.notification_id
+        ",
+        email_id,
+        error,
+        retry_at
+    )
     .execute(pool)
     .await?;
 
@@ -354,8 +1216,17 @@ pub async fn update_user_last_alerted_at(
 pub struct UserWithLocations {
     pub user_id: Uuid,
     pub email: String,
-    pub alert_threshold: AlertLevel,
+    pub alert_criterion: AlertCriterion,
+    pub min_visibility_score: i16,
+    pub max_cloud_cover: i16,
+    pub timezone: Tz,
+    pub quiet_hours: Option<QuietHours>,
+    pub alert_cooldown_minutes: i32,
+    pub notify_all_clear: bool,
+    pub verified: bool,
+    pub registered_at: DateTimeUtc,
     pub last_alerted_at: Option<DateTimeUtc>,
+    pub last_notified_level: Option<AlertLevel>,
     pub locations: Vec<Location>,
 }
 
@@ -369,6 +1240,13 @@ impl UserWithLocations {
                 weather_description: user_row.weather_description.clone(),
                 cloud_cover: user_row.cloud_cover,
                 updated_at: user_row.updated_at,
+                latitude: user_row.latitude,
+                longitude: user_row.longitude,
+                forecast: vec![],
+                sky: None,
+                visibility_score: None,
+                alert_threshold: Some(user_row.alert_threshold),
+                max_cloud_cover: user_row.location_max_cloud_cover,
             };
             locations.push(location);
         }
@@ -377,8 +1255,17 @@ impl UserWithLocations {
         UserWithLocations {
             user_id: row.user_id.clone(),
             email: row.email.clone(),
-            alert_threshold: row.alert_threshold.clone(),
+            alert_criterion: row.alert_criterion,
+            min_visibility_score: row.min_visibility_score,
+            max_cloud_cover: row.max_cloud_cover,
+            timezone: row.timezone(),
+            quiet_hours: row.quiet_hours(),
+            alert_cooldown_minutes: row.alert_cooldown_minutes,
+            notify_all_clear: row.notify_all_clear,
+            verified: row.verified,
+            registered_at: row.registered_at,
             last_alerted_at: row.last_alerted_at,
+            last_notified_level: row.last_notified_level,
             locations,
         }
     }
@@ -389,8 +1276,17 @@ impl UserWithLocations {
             let user_entry = user_map.entry(&user.user_id).or_insert(UserWithLocations {
                 user_id: user.user_id.clone(),
                 email: user.email.clone(),
-                alert_threshold: user.alert_threshold.clone(),
+                alert_criterion: user.alert_criterion,
+                min_visibility_score: user.min_visibility_score,
+                max_cloud_cover: user.max_cloud_cover,
+                timezone: user.timezone(),
+                quiet_hours: user.quiet_hours(),
+                alert_cooldown_minutes: user.alert_cooldown_minutes,
+                notify_all_clear: user.notify_all_clear,
+                verified: user.verified,
+                registered_at: user.registered_at,
                 last_alerted_at: user.last_alerted_at,
+                last_notified_level: user.last_notified_level,
                 locations: vec![],
             });
             let location = Location {
@@ -399,6 +1295,13 @@ impl UserWithLocations {
                 weather_description: user.weather_description.clone(),
                 cloud_cover: user.cloud_cover,
                 updated_at: user.updated_at,
+                latitude: user.latitude,
+                longitude: user.longitude,
+                forecast: vec![],
+                sky: None,
+                visibility_score: None,
+                alert_threshold: Some(user.alert_threshold),
+                max_cloud_cover: user.location_max_cloud_cover,
             };
             user_entry.locations.push(location);
         }
@@ -407,18 +1310,74 @@ impl UserWithLocations {
             .map(|(_user_id, user_location)| user_location)
             .collect()
     }
+
+    /// Fill in the cloud cover forecast for each of the user's locations.
+    pub fn attach_forecasts(&mut self, forecasts: &HashMap<i32, Vec<ForecastPoint>>) {
+        for location in &mut self.locations {
+            location.forecast = forecasts
+                .get(&location.location_id)
+                .cloned()
+                .unwrap_or_default();
+        }
+    }
+
+    /// Fill in the state of the sky at `time` for each of the user's locations.
+    pub fn attach_skies(&mut self, time: &DateTimeUtc) {
+        for location in &mut self.locations {
+            location.attach_sky(time);
+        }
+    }
+
+    /// Score the visibility of an aurora at each of the user's locations.
+    pub fn attach_visibility_scores(&mut self, alert_level: AlertLevel, moon: &MoonIllumination) {
+        for location in &mut self.locations {
+            location.attach_visibility_score(alert_level, moon);
+        }
+    }
 }
 
 struct UserWithLocationModel {
     user_id: Uuid,
     email: String,
-    alert_threshold: AlertLevel,
+    alert_criterion: AlertCriterion,
+    min_visibility_score: i16,
+    max_cloud_cover: i16,
+    timezone: String,
+    quiet_hours_start: Option<NaiveTime>,
+    quiet_hours_end: Option<NaiveTime>,
+    alert_cooldown_minutes: i32,
+    notify_all_clear: bool,
+    verified: bool,
+    registered_at: DateTimeUtc,
     last_alerted_at: Option<DateTimeUtc>,
+    last_notified_level: Option<AlertLevel>,
     location_id: i32,
     name: String,
     weather_description: String,
     cloud_cover: i16,
     updated_at: DateTimeUtc,
+    latitude: Option<f64>,
+    longitude: Option<f64>,
+    alert_threshold: AlertLevel,
+    location_max_cloud_cover: Option<i16>,
+}
+
+impl UserWithLocationModel {
+    /// The user's timezone, falling back to UTC if the stored name isn't
+    /// recognised.
+    fn timezone(&self) -> Tz {
+        self.timezone.parse().unwrap_or_else(|e| {
+            tracing::warn!("unrecognised timezone for user {}: {e}", self.user_id);
+            chrono_tz::UTC
+        })
+    }
+
+    fn quiet_hours(&self) -> Option<QuietHours> {
+        match (self.quiet_hours_start, self.quiet_hours_end) {
+            (Some(start), Some(end)) => Some(QuietHours { start, end }),
+            _ => None,
+        }
+    }
 }
 
 /// Return a list of all verified users.
@@ -429,13 +1388,27 @@ pub async fn get_verified_users(pool: &DbPool) -> Result<Vec<UserWithLocations>,
             SELECT
               users.user_id,
               users.email::TEXT as "email!",
-              users.alert_threshold as "alert_threshold:  AlertLevel",
               users.last_alerted_at as "last_alerted_at: DateTimeUtc",
+              users.alert_criterion as "alert_criterion: AlertCriterion",
+              users.min_visibility_score,
+              users.max_cloud_cover,
+              users.timezone,
+              users.quiet_hours_start,
+              users.quiet_hours_end,
+              users.alert_cooldown_minutes,
+              users.last_notified_level as "last_notified_level: AlertLevel",
+              users.notify_all_clear,
+              users.verified,
+              users.registered_at as "registered_at: DateTimeUtc",
               locations.location_id,
               locations.name::TEXT as "name!",
               locations.weather_description,
               locations.cloud_cover,
-              locations.updated_at as "updated_at: DateTimeUtc"
+              locations.updated_at as "updated_at: DateTimeUtc",
+              locations.latitude,
+              locations.longitude,
+              user_locations.alert_threshold as "alert_threshold: AlertLevel",
+              user_locations.max_cloud_cover as location_max_cloud_cover
             FROM
               users
             JOIN user_locations USING (user_id)
@@ -452,16 +1425,101 @@ pub async fn get_verified_users(pool: &DbPool) -> Result<Vec<UserWithLocations>,
     Ok(users)
 }
 
-/// Delete all unverified users from the database, returning a count of how many
-/// were deleted if successful.
-pub async fn delete_unverified_users(pool: &DbPool) -> Result<u64, anyhow::Error> {
+/// Return the unverified users who registered before `registered_before`, but
+/// after `expired_before`, and haven't yet been reminded to verify.
+pub async fn get_users_to_remind(
+    registered_before: &DateTimeUtc,
+    expired_before: &DateTimeUtc,
+    pool: &DbPool,
+) -> Result<Vec<UserWithLocations>, anyhow::Error> {
+    let users = sqlx::query_as!(
+        UserWithLocationModel,
+        r#"
+            SELECT
+              users.user_id,
+              users.email::TEXT as "email!",
+              users.last_alerted_at as "last_alerted_at: DateTimeUtc",
+              users.alert_criterion as "alert_criterion: AlertCriterion",
+              users.min_visibility_score,
+              users.max_cloud_cover,
+              users.timezone,
+              users.quiet_hours_start,
+              users.quiet_hours_end,
+              users.alert_cooldown_minutes,
+              users.last_notified_level as "last_notified_level: AlertLevel",
+              users.notify_all_clear,
+              users.verified,
+              users.registered_at as "registered_at: DateTimeUtc",
+              locations.location_id,
+              locations.name::TEXT as "name!",
+              locations.weather_description,
+              locations.cloud_cover,
+              locations.updated_at as "updated_at: DateTimeUtc",
+              locations.latitude,
+              locations.longitude,
+              user_locations.alert_threshold as "alert_threshold: AlertLevel",
+              user_locations.max_cloud_cover as location_max_cloud_cover
+            FROM
+              users
+            JOIN user_locations USING (user_id)
+            JOIN locations USING (location_id)
+            WHERE
+              NOT users.verified
+              AND users.verification_reminder_sent_at IS NULL
+              AND users.registered_at < $1
+              AND users.registered_at >= $2
+        "#,
+        registered_before,
+        expired_before
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let users = UserWithLocations::many_from_rows(users);
+
+    Ok(users)
+}
+
+/// Record that the user with the given id has been reminded to verify.
+pub async fn set_verification_reminder_sent<'c, E>(
+    user_id: &Uuid,
+    db: E,
+) -> Result<(), anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    sqlx::query!(
+        "
+            UPDATE
+              users
+            SET
+              verification_reminder_sent_at = now()
+            WHERE
+              user_id = $1
+        ",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete all users who registered before `registered_before` and remain
+/// unverified, returning a count of how many were deleted if successful.
+pub async fn delete_unverified_users(
+    registered_before: &DateTimeUtc,
+    pool: &DbPool,
+) -> Result<u64, anyhow::Error> {
     let deleted_users_count = sqlx::query!(
         r#"
             DELETE FROM 
               users
             WHERE 
               NOT verified
+              AND registered_at < $1
         "#,
+        registered_before
     )
     .execute(pool)
     .await?
@@ -470,60 +1528,72 @@ pub async fn delete_unverified_users(pool: &DbPool) -> Result<u64, anyhow::Error
     Ok(deleted_users_count)
 }
 
-/// Store the latest aurora activity data.
+/// Store the latest aurora activity data for a single station.
 pub async fn update_aurora_activity(
     activity: ActivityData,
     pool: &DbPool,
 ) -> Result<(), anyhow::Error> {
     let mut tx = pool.begin().await?;
-    let acts = activity.activities;
 
-    #[rustfmt::skip]
     sqlx::query!(
         "
-            INSERT INTO activity_data
-              (timestamp, value)
+            INSERT INTO stations
+              (station, updated_at)
             VALUES
-              ($1, $2), ($3, $4),  -- 2
-              ($5, $6), ($7, $8),  -- 4
-              ($9, $10), ($11, $12),  -- 6
-              ($13, $14), ($15, $16),  -- 8
-              ($17, $18), ($19, $20),  -- 10
-              ($21, $22), ($23, $24),  -- 12
-              ($25, $26), ($27, $28),  -- 14
-              ($29, $30), ($31, $32),  -- 16
-              ($33, $34), ($35, $36),  -- 18
-              ($37, $38), ($39, $40),  -- 20
-              ($41, $42), ($43, $44),  -- 22
-              ($45, $46), ($47, $48)   -- 24
-            ON CONFLICT (timestamp) DO UPDATE SET value = EXCLUDED.value
+              ($1, $2)
+            ON CONFLICT (station) DO UPDATE
+              -- Backfilled, archived data must not make the station look stale.
+              SET updated_at = GREATEST(stations.updated_at, EXCLUDED.updated_at)
         ",
-        // eugh!
-        acts[0].timestamp,  acts[0].value,  acts[1].timestamp,  acts[1].value,
-        acts[2].timestamp,  acts[2].value,  acts[3].timestamp,  acts[3].value,
-        acts[4].timestamp,  acts[4].value,  acts[5].timestamp,  acts[5].value,
-        acts[6].timestamp,  acts[6].value,  acts[7].timestamp,  acts[7].value,
-        acts[8].timestamp,  acts[8].value,  acts[9].timestamp,  acts[9].value,
-        acts[10].timestamp, acts[10].value, acts[11].timestamp, acts[11].value,
-        acts[12].timestamp, acts[12].value, acts[13].timestamp, acts[13].value,
-        acts[14].timestamp, acts[14].value, acts[15].timestamp, acts[15].value,
-        acts[16].timestamp, acts[16].value, acts[17].timestamp, acts[17].value,
-        acts[18].timestamp, acts[18].value, acts[19].timestamp, acts[19].value,
-        acts[20].timestamp, acts[20].value, acts[21].timestamp, acts[21].value,
-        acts[22].timestamp, acts[22].value, acts[23].timestamp, acts[23].value,
+        activity.station,
+        activity.updated_at
     )
     .execute(&mut tx)
     .await?;
 
+    if let Some(thresholds) = &activity.thresholds {
+        sqlx::query!(
+            "
+                INSERT INTO thresholds
+                  (station, green, yellow, amber, red)
+                VALUES
+                  ($1, $2, $3, $4, $5)
+                ON CONFLICT (station) DO UPDATE SET
+                  green = EXCLUDED.green,
+                  yellow = EXCLUDED.yellow,
+                  amber = EXCLUDED.amber,
+                  red = EXCLUDED.red
+            ",
+            activity.station,
+            thresholds.green,
+            thresholds.yellow,
+            thresholds.amber,
+            thresholds.red
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    let acts = &activity.activities;
+    let timestamps = acts.iter().map(|act| act.timestamp).collect::<Vec<_>>();
+    let values = acts.iter().map(|act| act.value).collect::<Vec<_>>();
+    let alert_levels = acts.iter().map(|act| act.alert_level).collect::<Vec<_>>();
+
     sqlx::query!(
         "
-            INSERT INTO activity_data_meta
-              (activity_data_meta_id, updated_at)
-            VALUES
-              (1, $1)
-            ON CONFLICT (activity_data_meta_id) DO UPDATE SET updated_at = EXCLUDED.updated_at
+            INSERT INTO activity_data
+              (station, timestamp, value, alert_level)
+            SELECT
+              $1::TEXT, *
+            FROM
+              UNNEST($2::TIMESTAMPTZ[], $3::REAL[], $4::alert_level_enum[])
+            ON CONFLICT (station, timestamp) DO UPDATE
+              SET value = EXCLUDED.value, alert_level = EXCLUDED.alert_level
         ",
-        activity.updated_at
+        activity.station,
+        &timestamps,
+        &values,
+        &alert_levels as &[AlertLevel]
     )
     .execute(&mut tx)
     .await?;
@@ -533,12 +1603,56 @@ pub async fn update_aurora_activity(
     Ok(())
 }
 
-/// Retrieve the activity data for the 24 hours ending at `end`.
+#[derive(Serialize)]
+pub struct Station {
+    pub station: String,
+    pub updated_at: DateTimeUtc,
+}
+
+/// Return all stations for which activity data has been stored.
+pub async fn get_stations(pool: &DbPool) -> Result<Vec<Station>, anyhow::Error> {
+    let stations = sqlx::query_as!(
+        Station,
+        r#"
+            SELECT
+              station,
+              updated_at as "updated_at: DateTimeUtc"
+            FROM
+              stations
+            ORDER BY
+              station ASC
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(stations)
+}
+
+/// The `date_trunc` field, and length of the interval, for each bucket of an
+/// aggregation.
+fn aggregation_unit(aggregation: Aggregation) -> &'static str {
+    match aggregation {
+        Aggregation::Hourly => "hour",
+        Aggregation::DailyMax => "day",
+        Aggregation::WeeklyMax => "week",
+    }
+}
+
+/// Retrieve the activity data recorded at `station` between `start` and `end`,
+/// with one data point per `aggregation` bucket.
+///
+/// Each data point is the maximum value, and highest alert level, recorded
+/// within its bucket, with empty buckets reported as 0.0 and green. The
+/// successful value is `None` if no data has ever been stored for the station.
 #[tracing::instrument()]
 pub async fn get_activity_data(
+    start: &DateTimeUtc,
     end: &DateTimeUtc,
+    aggregation: Aggregation,
+    station: &str,
     pool: &DbPool,
-) -> Result<ActivityData, anyhow::Error> {
+) -> Result<Option<ActivityData>, anyhow::Error> {
     let mut tx = pool.begin().await?;
 
     let updated_at = sqlx::query_scalar!(
@@ -546,41 +1660,113 @@ pub async fn get_activity_data(
             SELECT 
               updated_at as "updated_at: DateTimeUtc"
             FROM 
-              activity_data_meta
+              stations
             WHERE 
-              activity_data_meta_id = 1
-        "#
+              station = $1
+        "#,
+        station
     )
-    .fetch_one(&mut tx)
+    .fetch_optional(&mut tx)
     .await?;
 
+    let updated_at = match updated_at {
+        Some(updated_at) => updated_at,
+        None => return Ok(None),
+    };
+
     let activities = sqlx::query_as!(
         ActivityDataPoint,
         r#"
             SELECT 
-              timestamps.timestamp as "timestamp!",
-              COALESCE(activity_data.value, 0.0) as "value!"
+              buckets.timestamp as "timestamp!",
+              COALESCE(MAX(activity_data.value), 0.0) as "value!",
+              COALESCE(MAX(activity_data.alert_level), 'green') as "alert_level!: AlertLevel"
             FROM 
-              (
-                SELECT timestamp
-                FROM generate_series($1::timestamptz - '23 hours'::interval, $1::timestamptz, '1 hour'::interval) t(timestamp)
-              ) as timestamps
+              generate_series(
+                date_trunc($3, $1::timestamptz),
+                $2::timestamptz,
+                ('1 ' || $3)::interval
+              ) as buckets(timestamp)
             LEFT OUTER JOIN
-              activity_data ON timestamps.timestamp = activity_data.timestamp
+              activity_data
+                ON activity_data.station = $4
+                AND activity_data.timestamp >= buckets.timestamp
+                AND activity_data.timestamp < buckets.timestamp + ('1 ' || $3)::interval
+            GROUP BY
+              buckets.timestamp
             ORDER BY
-            timestamps.timestamp DESC;
+              buckets.timestamp DESC;
         "#,
-        end
+        start,
+        end,
+        aggregation_unit(aggregation),
+        station
     )
     .fetch_all(&mut tx)
     .await?;
 
+    let thresholds = get_thresholds(station, &mut tx).await?;
+
     tx.commit().await?;
 
-    Ok(ActivityData {
+    Ok(Some(ActivityData {
+        station: station.to_string(),
         updated_at,
-        // Safe to unwrap because our generate_series function will always return get 24 rows,
-        // and we coalesce null (non-existent) values to 0.0.
-        activities: activities.try_into().unwrap(),
-    })
+        activities,
+        thresholds,
+    }))
+}
+
+/// Retrieve the alert level thresholds reported by `station`, if any.
+pub async fn get_thresholds<'c, E>(
+    station: &str,
+    db: E,
+) -> Result<Option<Thresholds>, anyhow::Error>
+where
+    E: sqlx::PgExecutor<'c>,
+{
+    let thresholds = sqlx::query_as!(
+        Thresholds,
+        r#"
+            SELECT
+              green,
+              yellow,
+              amber,
+              red
+            FROM
+              thresholds
+            WHERE
+              station = $1
+        "#,
+        station
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(thresholds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `#[sqlx::test]` runs every migration against a fresh database first.
+    #[sqlx::test]
+    async fn test_migrations_allow_weather_updates(pool: DbPool) {
+        let weather = apis::weather::Weather {
+            description: "clear sky".to_string(),
+            cloud_cover: 10,
+            latitude: None,
+            longitude: None,
+        };
+
+        update_weather(weather, 2649169, &pool).await.unwrap();
+
+        let location = get_location(2649169, &pool).await.unwrap().unwrap();
+        assert_eq!(location.weather_description, "clear sky");
+        assert_eq!(location.cloud_cover, 10);
+        assert_eq!(location.latitude, Some(56.8198));
+        assert_eq!(location.longitude, Some(-5.1052));
+        assert!(location.updated_at > chrono::Utc::now() - chrono::Duration::minutes(1));
+    }
 }
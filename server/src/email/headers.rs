@@ -0,0 +1,45 @@
+//! Headers which lettre doesn't provide.
+//!
+//! See RFC 2369 and RFC 8058 for the unsubscribe headers.
+
+use std::error::Error;
+
+use lettre::message::header::{Header, HeaderName, HeaderValue};
+
+/// The URL a mail client can use to unsubscribe the recipient.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListUnsubscribe(pub String);
+
+impl Header for ListUnsubscribe {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe")
+    }
+
+    fn parse(s: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let url = s.trim().trim_start_matches('<').trim_end_matches('>');
+        Ok(Self(url.to_string()))
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), format!("<{}>", self.0))
+    }
+}
+
+/// Signals that the `List-Unsubscribe` URL unsubscribes the recipient with a
+/// single POST request, without any further interaction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListUnsubscribePost;
+
+impl Header for ListUnsubscribePost {
+    fn name() -> HeaderName {
+        HeaderName::new_from_ascii_str("List-Unsubscribe-Post")
+    }
+
+    fn parse(_s: &str) -> Result<Self, Box<dyn Error + Send + Sync>> {
+        Ok(Self)
+    }
+
+    fn display(&self) -> HeaderValue {
+        HeaderValue::new(Self::name(), "List-Unsubscribe=One-Click".to_string())
+    }
+}
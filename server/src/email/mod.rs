@@ -0,0 +1,542 @@
+use std::sync::Arc;
+
+use lettre::{
+    message::{header, Mailbox, MultiPart},
+    Message,
+};
+use reqwest::Url;
+use tera::Context;
+use tera::Tera;
+use uuid::Uuid;
+
+use crate::astronomy::MoonIllumination;
+use crate::common::AlertLevel;
+use crate::configuration::EmailSettings;
+use crate::db;
+use crate::templates;
+use crate::templates::{RenderedTemplate, Template};
+use crate::tokens::{TokenPurpose, TokenSigner};
+use headers::{ListUnsubscribe, ListUnsubscribePost};
+use transport::EmailTransport;
+
+mod headers;
+pub mod transport;
+
+/// Coalesce all possible errors in this module into one type.
+#[derive(Debug, thiserror::Error)]
+pub enum EmailError {
+    #[error("Lettre error: {0}")]
+    Lettre(#[from] lettre::error::Error),
+
+    #[error("Smtp error: {0}")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+
+    #[error("Address error: {0}")]
+    Address(#[from] lettre::address::AddressError),
+
+    #[error("Content type error: {0}")]
+    ContentType(#[from] lettre::message::header::ContentTypeErr),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl EmailError {
+    /// Whether retrying the email would be pointless, because it would fail in
+    /// the same way again.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            Self::Smtp(e) => e.is_permanent(),
+            Self::Io(_) => false,
+            _ => true,
+        }
+    }
+}
+
+pub struct AlertBuilder {
+    template_engine: Tera,
+    template: Template,
+    to_address: String,
+    links: Links,
+}
+
+impl AlertBuilder {
+    fn new(to_address: &str, template_engine: Tera, links: Links) -> Self {
+        let template = Template::Alert;
+        Self {
+            template_engine,
+            template,
+            to_address: to_address.to_string(),
+            links,
+        }
+    }
+
+    pub fn add_context(
+        self,
+        user: &db::UserWithLocations,
+        alert_level: &AlertLevel,
+    ) -> Result<RenderedEmailBuilder, anyhow::Error> {
+        let mut context = Context::from_serialize(user)?;
+        self.links.insert(&mut context, &user.user_id)?;
+        context.insert("alert_level", alert_level);
+
+        let moon = MoonIllumination::at(&chrono::Utc::now());
+        context.insert("moon_illumination", &moon.percentage());
+        context.insert("moon_phase", moon.phase_name());
+
+        let body = self.template.render(&context, &self.template_engine)?;
+
+        Ok(RenderedEmailBuilder {
+            to_address: self.to_address,
+            subject: format!("Aurora alert level is now {alert_level}"),
+            body,
+            list_unsubscribe: Some(self.links.one_click_unsubscribe(&user.user_id)?),
+        })
+    }
+}
+
+pub struct AllClearBuilder {
+    template_engine: Tera,
+    template: Template,
+    to_address: String,
+    links: Links,
+}
+
+impl AllClearBuilder {
+    fn new(to_address: &str, template_engine: Tera, links: Links) -> Self {
+        let template = Template::AllClear;
+        Self {
+            template_engine,
+            template,
+            to_address: to_address.to_string(),
+            links,
+        }
+    }
+
+    pub fn add_context(
+        self,
+        user: &db::UserWithLocations,
+        alert_level: &AlertLevel,
+    ) -> Result<RenderedEmailBuilder, anyhow::Error> {
+        let mut context = Context::from_serialize(user)?;
+        self.links.insert(&mut context, &user.user_id)?;
+        context.insert("alert_level", alert_level);
+
+        let body = self.template.render(&context, &self.template_engine)?;
+
+        Ok(RenderedEmailBuilder {
+            to_address: self.to_address,
+            subject: format!("Aurora alert level has fallen to {alert_level}"),
+            body,
+            list_unsubscribe: Some(self.links.one_click_unsubscribe(&user.user_id)?),
+        })
+    }
+}
+
+pub struct VerifyUserBuilder {
+    template_engine: Tera,
+    template: Template,
+    to_address: String,
+    subject: String,
+    links: Links,
+    verification_window: chrono::Duration,
+    reminder: bool,
+}
+
+impl VerifyUserBuilder {
+    fn new(
+        to_address: &str,
+        template_engine: Tera,
+        links: Links,
+        verification_window: chrono::Duration,
+    ) -> Self {
+        let template = Template::VerifyUser;
+        Self {
+            template_engine,
+            template,
+            to_address: to_address.to_string(),
+            subject: String::from("Welcome to Aurora Alert"),
+            links,
+            verification_window,
+            reminder: false,
+        }
+    }
+
+    /// Remind the user that they still need to verify, rather than welcoming
+    /// them.
+    fn reminder(mut self) -> Self {
+        self.subject = String::from("Reminder: verify your Aurora Alert account");
+        self.reminder = true;
+        self
+    }
+
+    pub fn add_context(
+        self,
+        user: &db::UserWithLocations,
+    ) -> Result<RenderedEmailBuilder, anyhow::Error> {
+        let mut context = templates::Context::from_serialize(user)?;
+        self.links.insert(&mut context, &user.user_id)?;
+        context.insert(
+            "verify_url",
+            self.links
+                .url("/verify", TokenPurpose::Verify, &user.user_id)?
+                .as_str(),
+        );
+        context.insert(
+            "verify_by",
+            &(user.registered_at + self.verification_window),
+        );
+        context.insert("reminder", &self.reminder);
+
+        let body = self.template.render(&context, &self.template_engine)?;
+
+        Ok(RenderedEmailBuilder {
+            to_address: self.to_address,
+            subject: self.subject,
+            body,
+            list_unsubscribe: None,
+        })
+    }
+}
+
+pub struct UserAlreadyRegisteredBuilder {
+    template_engine: Tera,
+    template: Template,
+    to_address: String,
+    subject: String,
+    links: Links,
+}
+
+impl UserAlreadyRegisteredBuilder {
+    fn new(to_address: &str, template_engine: Tera, links: Links) -> Self {
+        let template = Template::UserAlreadyRegistered;
+
+        Self {
+            template_engine,
+            template,
+            to_address: to_address.to_string(),
+            subject: String::from("Re-registering to Aurora Alert"),
+            links,
+        }
+    }
+
+    pub fn add_context(
+        self,
+        user: &db::UserWithLocations,
+    ) -> Result<RenderedEmailBuilder, anyhow::Error> {
+        let mut context = templates::Context::from_serialize(user)?;
+        self.links.insert(&mut context, &user.user_id)?;
+        context.insert(
+            "manage_url",
+            self.links
+                .url("/manage", TokenPurpose::Manage, &user.user_id)?
+                .as_str(),
+        );
+        let body = self.template.render(&context, &self.template_engine)?;
+
+        Ok(RenderedEmailBuilder {
+            to_address: self.to_address,
+            subject: self.subject,
+            body,
+            list_unsubscribe: None,
+        })
+    }
+}
+
+pub struct ManageSubscriptionBuilder {
+    template_engine: Tera,
+    template: Template,
+    to_address: String,
+    subject: String,
+    links: Links,
+}
+
+impl ManageSubscriptionBuilder {
+    fn new(to_address: &str, template_engine: Tera, links: Links) -> Self {
+        let template = Template::ManageSubscription;
+
+        Self {
+            template_engine,
+            template,
+            to_address: to_address.to_string(),
+            subject: String::from("Manage your Aurora Alert subscription"),
+            links,
+        }
+    }
+
+    pub fn add_context(
+        self,
+        user: &db::UserWithLocations,
+    ) -> Result<RenderedEmailBuilder, anyhow::Error> {
+        let mut context = templates::Context::from_serialize(user)?;
+        self.links.insert(&mut context, &user.user_id)?;
+        context.insert(
+            "manage_url",
+            self.links
+                .url("/manage", TokenPurpose::Manage, &user.user_id)?
+                .as_str(),
+        );
+        let body = self.template.render(&context, &self.template_engine)?;
+
+        Ok(RenderedEmailBuilder {
+            to_address: self.to_address,
+            subject: self.subject,
+            body,
+            list_unsubscribe: None,
+        })
+    }
+}
+
+pub struct RenderedEmailBuilder {
+    to_address: String,
+    subject: String,
+    body: RenderedTemplate,
+    /// The one-click unsubscribe URL, for emails sent on an ongoing basis.
+    list_unsubscribe: Option<String>,
+}
+
+impl RenderedEmailBuilder {
+    /// Enqueue the email in the outbox, from which it will be delivered.
+    ///
+    /// The recipient is checked to be valid first, so that the email doesn't sit
+    /// in the outbox failing to send.
+    pub async fn enqueue<'c, E>(
+        self,
+        notification_id: Option<Uuid>,
+        db: E,
+    ) -> Result<Uuid, anyhow::Error>
+    where
+        E: sqlx::PgExecutor<'c>,
+    {
+        self.to_address.parse::<Mailbox>()?;
+
+        db::enqueue_email(
+            &self.to_address,
+            &self.subject,
+            &self.body.html,
+            &self.body.text,
+            self.list_unsubscribe.as_deref(),
+            notification_id,
+            db,
+        )
+        .await
+    }
+}
+
+/// Builds the links in emails, which identify the recipient with signed
+/// tokens rather than their details.
+#[derive(Clone, Debug)]
+struct Links {
+    /// The public URL of the frontend, without a trailing slash.
+    base_url: String,
+    signer: TokenSigner,
+}
+
+impl Links {
+    /// A link to `path` under the base URL, with a token for the given purpose.
+    fn url(&self, path: &str, purpose: TokenPurpose, user_id: &Uuid) -> Result<Url, anyhow::Error> {
+        let token = self.signer.sign(purpose, user_id);
+        let url = Url::parse_with_params(&format!("{}{path}", self.base_url), &[("token", token)])?;
+
+        Ok(url)
+    }
+
+    /// Add the links included in every email to the template context.
+    fn insert(&self, context: &mut Context, user_id: &Uuid) -> Result<(), anyhow::Error> {
+        context.insert("base_url", &self.base_url);
+        context.insert(
+            "unsubscribe_url",
+            self.url("/unsubscribe", TokenPurpose::Unsubscribe, user_id)?
+                .as_str(),
+        );
+
+        Ok(())
+    }
+
+    /// The API endpoint which unsubscribes a user with a single POST request, as
+    /// described in RFC 8058.
+    fn one_click_unsubscribe(&self, user_id: &Uuid) -> Result<String, anyhow::Error> {
+        Ok(self
+            .url("/api/users/unsubscribe", TokenPurpose::Unsubscribe, user_id)?
+            .to_string())
+    }
+}
+
+/// Build an email from the outbox, with a plain text alternative to the HTML
+/// body and unsubscribe headers if it has them.
+fn build_message(sender: &Mailbox, email: &db::OutboxEmail) -> Result<Message, EmailError> {
+    let mut builder = Message::builder()
+        .from(sender.clone())
+        .to(email.to_address.parse()?)
+        .subject(&email.subject);
+    if let Some(url) = &email.list_unsubscribe {
+        builder = builder
+            .header(ListUnsubscribe(url.clone()))
+            .header(ListUnsubscribePost);
+    }
+
+    let message = match &email.text_body {
+        Some(text_body) => builder.multipart(MultiPart::alternative_plain_html(
+            text_body.clone(),
+            email.body.clone(),
+        ))?,
+        None => builder
+            .header(header::ContentType::parse("text/html; charset=utf8")?)
+            .body(email.body.clone())?,
+    };
+
+    Ok(message)
+}
+
+/// Entry point for constructing an email which can be sent to the given
+/// address.
+#[derive(Clone, Debug)]
+pub struct EmailClient {
+    transport: Arc<dyn EmailTransport>,
+    sender: Mailbox,
+    links: Links,
+    /// How long new users have to verify, which verification emails mention.
+    verification_window: chrono::Duration,
+    pub template_engine: Tera,
+}
+
+impl EmailClient {
+    pub fn new(
+        config: &EmailSettings,
+        base_url: &str,
+        signer: TokenSigner,
+        verification_window: chrono::Duration,
+    ) -> Result<Self, EmailError> {
+        let transport = transport::from_settings(&config.transport)?;
+        let sender = config.sender.parse()?;
+
+        let template_engine =
+            templates::init().expect("failed to initialise email template engine");
+
+        Ok(Self {
+            transport,
+            sender,
+            links: Links {
+                base_url: base_url.trim_end_matches('/').to_string(),
+                signer,
+            },
+            verification_window,
+            template_engine,
+        })
+    }
+
+    /// Start constructing a new email for sending out an aurora alert.
+    pub fn new_alert(&self, to_address: &str) -> AlertBuilder {
+        let engine = self.template_engine.clone();
+        AlertBuilder::new(to_address, engine, self.links.clone())
+    }
+
+    /// Start constructing an email to let a user know that the activity they
+    /// were alerted about has subsided.
+    pub fn new_all_clear(&self, to_address: &str) -> AllClearBuilder {
+        let engine = self.template_engine.clone();
+        AllClearBuilder::new(to_address, engine, self.links.clone())
+    }
+
+    /// Start constructing an email to verify a new user's identity.
+    pub fn new_verify_user(&self, to_address: &str) -> VerifyUserBuilder {
+        let engine = self.template_engine.clone();
+        VerifyUserBuilder::new(
+            to_address,
+            engine,
+            self.links.clone(),
+            self.verification_window,
+        )
+    }
+
+    /// Start constructing an email to remind a new user to verify their
+    /// identity before their account is deleted.
+    pub fn new_verification_reminder(&self, to_address: &str) -> VerifyUserBuilder {
+        self.new_verify_user(to_address).reminder()
+    }
+
+    /// Start constructing an email for a user who has tried to register an
+    /// existing email address.
+    pub fn new_user_already_registered(&self, to_address: &str) -> UserAlreadyRegisteredBuilder {
+        let engine = self.template_engine.clone();
+        UserAlreadyRegisteredBuilder::new(to_address, engine, self.links.clone())
+    }
+
+    /// Start constructing an email with a link for a user to manage their
+    /// subscription.
+    pub fn new_manage_subscription(&self, to_address: &str) -> ManageSubscriptionBuilder {
+        let engine = self.template_engine.clone();
+        ManageSubscriptionBuilder::new(to_address, engine, self.links.clone())
+    }
+
+    /// Deliver an email from the outbox to the mail server.
+    pub async fn deliver(&self, email: &db::OutboxEmail) -> Result<(), EmailError> {
+        let message = build_message(&self.sender, email)?;
+
+        self.transport.send(message).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::TokenSettings;
+    use transport::MemoryTransport;
+
+    #[tokio::test]
+    async fn test_deliver_multipart_from_configured_sender() {
+        let transport = MemoryTransport::default();
+        let email_client = EmailClient {
+            transport: Arc::new(transport.clone()),
+            sender: "Aurora Alert <alerts@example.com>".parse().unwrap(),
+            links: Links {
+                base_url: "https://aurora-alert.example.com".to_string(),
+                signer: TokenSigner::new(&TokenSettings {
+                    secret: "a secret which is at least 32 bytes long".to_string(),
+                    verify_ttl_hours: 72,
+                    unsubscribe_ttl_hours: 2160,
+                    manage_ttl_hours: 24,
+                })
+                .unwrap(),
+            },
+            verification_window: chrono::Duration::hours(48),
+            template_engine: templates::init().unwrap(),
+        };
+        let email = db::OutboxEmail {
+            email_id: Uuid::new_v4(),
+            to_address: "user@example.com".to_string(),
+            subject: "Aurora alert level is now red".to_string(),
+            body: "<p>Hi there,</p>".to_string(),
+            text_body: Some("Hi there,".to_string()),
+            list_unsubscribe: Some(
+                "https://aurora-alert.example.com/api/users/unsubscribe?token=abc".to_string(),
+            ),
+            attempts: 0,
+        };
+
+        email_client.deliver(&email).await.unwrap();
+
+        let messages = transport.messages();
+        assert_eq!(messages.len(), 1);
+        let formatted = String::from_utf8(messages[0].formatted()).unwrap();
+        let header = |name: &str| {
+            formatted
+                .lines()
+                .find(|line| line.starts_with(name))
+                .unwrap_or_default()
+                .to_string()
+        };
+        assert!(header("From: ").contains("alerts@example.com"));
+        assert!(header("To: ").contains("user@example.com"));
+        assert!(header("Content-Type: ").contains("multipart/alternative"));
+        assert!(formatted.contains("text/plain"));
+        assert_eq!(
+            header("List-Unsubscribe: "),
+            "List-Unsubscribe: <https://aurora-alert.example.com/api/users/unsubscribe?token=abc>"
+        );
+        assert_eq!(
+            header("List-Unsubscribe-Post: "),
+            "List-Unsubscribe-Post: List-Unsubscribe=One-Click"
+        );
+    }
+}
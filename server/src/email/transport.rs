@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use uuid::Uuid;
+
+use super::EmailError;
+use crate::configuration::{EmailTransportSettings, SmtpTlsMode};
+
+/// Somewhere emails can be delivered to.
+#[async_trait]
+pub trait EmailTransport: std::fmt::Debug + Send + Sync {
+    async fn send(&self, message: Message) -> Result<(), EmailError>;
+}
+
+/// Build the transport chosen in the settings.
+pub fn from_settings(
+    settings: &EmailTransportSettings,
+) -> Result<Arc<dyn EmailTransport>, EmailError> {
+    let transport: Arc<dyn EmailTransport> = match settings {
+        EmailTransportSettings::Smtp {
+            host,
+            port,
+            tls,
+            username,
+            password,
+        } => {
+            let credentials = match (username, password) {
+                (Some(username), Some(password)) => {
+                    Some(Credentials::new(username.clone(), password.clone()))
+                }
+                _ => None,
+            };
+            Arc::new(SmtpTransport::new(host, *port, *tls, credentials)?)
+        }
+        EmailTransportSettings::File { directory } => Arc::new(FileTransport::new(directory)),
+        EmailTransportSettings::Memory => Arc::new(MemoryTransport::default()),
+    };
+
+    Ok(transport)
+}
+
+/// Delivers emails to an SMTP server.
+#[derive(Debug)]
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    pub fn new(
+        host: &str,
+        port: u16,
+        tls: SmtpTlsMode,
+        credentials: Option<Credentials>,
+    ) -> Result<Self, EmailError> {
+        let builder = match tls {
+            SmtpTlsMode::Wrapper => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpTlsMode::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpTlsMode::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        }
+        .port(port);
+        let builder = match credentials {
+            Some(credentials) => builder.credentials(credentials),
+            None => builder,
+        };
+
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, message: Message) -> Result<(), EmailError> {
+        self.mailer.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Writes each email to its own `.eml` file, which most mail clients can open.
+#[derive(Debug)]
+pub struct FileTransport {
+    directory: PathBuf,
+}
+
+impl FileTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileTransport {
+    async fn send(&self, message: Message) -> Result<(), EmailError> {
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let path = self.directory.join(format!("{}.eml", Uuid::new_v4()));
+        tokio::fs::write(&path, message.formatted()).await?;
+        tracing::debug!("email written to {}", path.display());
+
+        Ok(())
+    }
+}
+
+/// Keeps emails in memory, so that tests can check what would have been sent.
+///
+/// Clones share the same emails.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryTransport {
+    messages: Arc<Mutex<Vec<Message>>>,
+}
+
+impl MemoryTransport {
+    /// All the emails sent so far, oldest first.
+    #[cfg(test)]
+    pub fn messages(&self) -> Vec<Message> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl EmailTransport for MemoryTransport {
+    async fn send(&self, message: Message) -> Result<(), EmailError> {
+        self.messages.lock().unwrap().push(message);
+        Ok(())
+    }
+}
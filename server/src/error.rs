@@ -4,6 +4,14 @@ use axum::response::{IntoResponse, Response};
 /// An enumeration of all possible error variants.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    /// Return `400 Bad Request`, with the given explanation.
+    #[error("bad request: {0}")]
+    BadRequest(String),
+
+    /// Return `401 Unauthorized`.
+    #[error("unauthorized")]
+    Unauthorized,
+
     /// Return `404 Not Found`.
     #[error("request path not found")]
     NotFound,
@@ -11,9 +19,6 @@ pub enum Error {
     #[error("database error: {0}")]
     Database(#[from] sqlx::Error),
 
-    #[error("email error: {0}")]
-    Email(#[from] crate::email::EmailError),
-
     #[error("template error: {0}")]
     Template(#[from] tera::Error),
 
@@ -25,6 +30,8 @@ impl Error {
     /// Associate a HTTP status code with each error variant.
     fn status_code(&self) -> StatusCode {
         match self {
+            Self::BadRequest(_) => StatusCode::BAD_REQUEST,
+            Self::Unauthorized => StatusCode::UNAUTHORIZED,
             Self::NotFound => StatusCode::NOT_FOUND,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -37,8 +44,6 @@ impl Error {
         match self {
             Self::Database(_) => "an error occurred with the database".to_string(),
 
-            Self::Email(_) => "an error occurred with the email system".to_string(),
-
             Self::Template(_) => "an error occurred with the template system".to_string(),
 
             Self::Anyhow(_) => "an internal server error occurred".to_string(),
@@ -60,10 +65,6 @@ impl IntoResponse for Error {
                 tracing::error!("database error: {:?}", e);
             }
 
-            Self::Email(ref e) => {
-                tracing::error!("email error: {:?}", e);
-            }
-
             Self::Template(ref e) => {
                 tracing::error!("template error: {:?}", e);
             }
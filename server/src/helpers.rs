@@ -1,13 +1,260 @@
+use chrono::NaiveTime;
+use serde::Serialize;
+
 use super::db;
-use crate::common::AlertLevel;
+use crate::astronomy::Darkness;
+use crate::common::{AlertCriterion, AlertLevel};
+use crate::types::DateTimeUtc;
 
+/// Whether the user should be sent an alert.
+///
+/// The user mustn't have been alerted within their cooldown, unless the alert
+/// level has escalated since, nor be within their quiet hours. At least one of
+/// their locations must also meet their alert criterion while being dark and
+/// clear enough to see an aurora. If the user is alerted on the visibility
+/// score, the scores must have been attached to their locations first.
 pub fn should_alert_user(user: &db::UserWithLocations, alert_level: &AlertLevel) -> bool {
-    if user.alert_threshold <= *alert_level {
-        let now = chrono::Utc::now();
-        let two_hours_ago = now - chrono::Duration::hours(2);
-        if user.last_alerted_at.is_none() || user.last_alerted_at.unwrap() < two_hours_ago {
-            return true;
-        }
+    let now = chrono::Utc::now();
+    let cooldown_start = now - chrono::Duration::minutes(user.alert_cooldown_minutes.into());
+    let escalated = user
+        .last_notified_level
+        .map_or(false, |last_notified_level| {
+            *alert_level > last_notified_level
+        });
+    if (user.last_alerted_at.is_none()
+        || user.last_alerted_at.unwrap() < cooldown_start
+        || escalated)
+        && !in_quiet_hours(user, &now)
+    {
+        return user.locations.iter().any(|location| {
+            meets_alert_criterion(user, location, alert_level) && is_viewable(user, location, &now)
+        });
     }
     false
 }
+
+/// Whether activity has subsided since the user was last alerted.
+///
+/// For users alerted on the alert level, this is once it falls below the
+/// threshold of every location. The visibility score depends on the weather as
+/// well, so for users alerted on it, this is once the alert level is back to
+/// green.
+pub fn is_all_clear(user: &db::UserWithLocations, alert_level: &AlertLevel) -> bool {
+    if user.last_notified_level.is_none() {
+        return false;
+    }
+    match user.alert_criterion {
+        AlertCriterion::AlertLevel => user.locations.iter().all(|location| {
+            location
+                .alert_threshold
+                .map_or(true, |threshold| *alert_level < threshold)
+        }),
+        AlertCriterion::VisibilityScore => *alert_level == AlertLevel::Green,
+    }
+}
+
+/// The level to record as the one the user was last notified about, if
+/// activity has dropped since they were alerted, but not far enough for an all
+/// clear.
+///
+/// Recording the lower level means that a rise back up counts as an escalation,
+/// which is alerted on even within the cooldown.
+pub fn lowered_notified_level(
+    user: &db::UserWithLocations,
+    alert_level: &AlertLevel,
+) -> Option<AlertLevel> {
+    match user.last_notified_level {
+        Some(last_notified_level)
+            if *alert_level < last_notified_level && !is_all_clear(user, alert_level) =>
+        {
+            Some(*alert_level)
+        }
+        _ => None,
+    }
+}
+
+/// Whether it is currently within the user's quiet hours.
+pub fn in_quiet_hours(user: &db::UserWithLocations, time: &DateTimeUtc) -> bool {
+    user.quiet_hours.map_or(false, |quiet_hours| {
+        quiet_hours.contains(time.with_timezone(&user.timezone).time())
+    })
+}
+
+fn meets_alert_criterion(
+    user: &db::UserWithLocations,
+    location: &db::Location,
+    alert_level: &AlertLevel,
+) -> bool {
+    match user.alert_criterion {
+        AlertCriterion::AlertLevel => location
+            .alert_threshold
+            .map_or(false, |threshold| threshold <= *alert_level),
+        AlertCriterion::VisibilityScore => location
+            .visibility_score
+            .map_or(false, |score| score >= user.min_visibility_score),
+    }
+}
+
+/// Whether the location is both dark enough, and clear enough for the user's
+/// liking, to see an aurora.
+///
+/// Locations without coordinates are assumed to be dark, so that users aren't
+/// silently missed out.
+fn is_viewable(user: &db::UserWithLocations, location: &db::Location, time: &DateTimeUtc) -> bool {
+    let is_dark = match (location.latitude, location.longitude) {
+        (Some(latitude), Some(longitude)) => Darkness::at(time, latitude, longitude).is_dark(),
+        _ => {
+            // Rather than withholding alerts until the coordinates are filled in, only
+            // the cloud cover is checked.
+            tracing::warn!(
+                "location {} has no coordinates, skipping the darkness check",
+                location.location_id
+            );
+            true
+        }
+    };
+    let max_cloud_cover = location.max_cloud_cover.unwrap_or(user.max_cloud_cover);
+    is_dark && location.cloud_cover <= max_cloud_cover
+}
+
+/// A period of each day, in a user's local time, during which they don't want
+/// to be emailed.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Whether the local `time` falls within the quiet hours, which may span
+    /// midnight.
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn test_quiet_hours_spanning_midnight() {
+        let quiet_hours = QuietHours {
+            start: NaiveTime::from_hms(23, 0, 0),
+            end: NaiveTime::from_hms(7, 0, 0),
+        };
+        assert!(quiet_hours.contains(NaiveTime::from_hms(23, 30, 0)));
+        assert!(quiet_hours.contains(NaiveTime::from_hms(1, 0, 0)));
+        assert!(!quiet_hours.contains(NaiveTime::from_hms(7, 0, 0)));
+        assert!(!quiet_hours.contains(NaiveTime::from_hms(12, 0, 0)));
+    }
+
+    fn location(latitude: Option<f64>, longitude: Option<f64>) -> db::Location {
+        db::Location {
+            location_id: 2649169,
+            name: "Fort William".to_string(),
+            weather_description: "clear sky".to_string(),
+            cloud_cover: 10,
+            updated_at: chrono::Utc::now(),
+            latitude,
+            longitude,
+            forecast: vec![],
+            sky: None,
+            visibility_score: None,
+            alert_threshold: Some(AlertLevel::Yellow),
+            max_cloud_cover: None,
+        }
+    }
+
+    fn user(locations: Vec<db::Location>) -> db::UserWithLocations {
+        db::UserWithLocations {
+            user_id: uuid::Uuid::nil(),
+            email: "user@example.com".to_string(),
+            alert_criterion: AlertCriterion::AlertLevel,
+            min_visibility_score: 50,
+            max_cloud_cover: 50,
+            timezone: chrono_tz::Europe::London,
+            quiet_hours: None,
+            alert_cooldown_minutes: 60,
+            notify_all_clear: true,
+            verified: true,
+            registered_at: chrono::Utc::now(),
+            last_alerted_at: None,
+            last_notified_level: None,
+            locations,
+        }
+    }
+
+    /// A user who was last alerted at `last_notified_level` 10 minutes ago, within
+    /// their cooldown, for a location which is clear enough to see an aurora.
+    fn recently_alerted_user(last_notified_level: AlertLevel) -> db::UserWithLocations {
+        let mut user = user(vec![location(None, None)]);
+        user.last_alerted_at = Some(chrono::Utc::now() - chrono::Duration::minutes(10));
+        user.last_notified_level = Some(last_notified_level);
+        user
+    }
+
+    #[test]
+    fn test_escalation_within_cooldown_alerts() {
+        let user = recently_alerted_user(AlertLevel::Yellow);
+        assert!(should_alert_user(&user, &AlertLevel::Red));
+    }
+
+    #[test]
+    fn test_same_level_within_cooldown_does_not_alert() {
+        let user = recently_alerted_user(AlertLevel::Yellow);
+        assert!(!should_alert_user(&user, &AlertLevel::Yellow));
+    }
+
+    #[test]
+    fn test_rise_after_partial_drop_within_cooldown_alerts() {
+        let mut user = recently_alerted_user(AlertLevel::Red);
+
+        assert!(!should_alert_user(&user, &AlertLevel::Yellow));
+        let lowered = lowered_notified_level(&user, &AlertLevel::Yellow);
+        assert_eq!(lowered, Some(AlertLevel::Yellow));
+        user.last_notified_level = lowered;
+
+        assert!(should_alert_user(&user, &AlertLevel::Red));
+    }
+
+    #[test]
+    fn test_all_clear_once_below_every_location_threshold() {
+        let mut amber_location = location(None, None);
+        amber_location.alert_threshold = Some(AlertLevel::Amber);
+        let mut user = user(vec![location(None, None), amber_location]);
+        user.last_notified_level = Some(AlertLevel::Amber);
+
+        assert!(!is_all_clear(&user, &AlertLevel::Yellow));
+        assert!(is_all_clear(&user, &AlertLevel::Green));
+    }
+
+    #[test]
+    fn test_no_all_clear_for_user_never_notified() {
+        let user = user(vec![location(None, None)]);
+        assert!(!is_all_clear(&user, &AlertLevel::Green));
+    }
+
+    #[test]
+    fn test_location_without_coordinates_is_only_checked_for_cloud_cover() {
+        let midday = chrono::Utc.ymd(2022, 12, 21).and_hms(12, 0, 0);
+        let user = user(vec![]);
+
+        assert!(!is_viewable(
+            &user,
+            &location(Some(56.8198), Some(-5.1052)),
+            &midday
+        ));
+        assert!(is_viewable(&user, &location(None, None), &midday));
+
+        let mut cloudy = location(None, None);
+        cloudy.cloud_cover = 90;
+        assert!(!is_viewable(&user, &cloudy, &midday));
+    }
+}
@@ -1,4 +1,6 @@
 mod apis;
+mod astronomy;
+pub mod backfill;
 pub mod configuration;
 mod db;
 mod email;
@@ -9,6 +11,8 @@ pub mod startup;
 pub mod tasks;
 pub mod telemetry;
 mod templates;
+mod tokens;
 mod types;
+mod visibility;
 
 use common;
@@ -2,8 +2,11 @@ use std::fmt::{Debug, Display};
 
 use server::{
     configuration::get_configuration,
-    startup::Application,
-    tasks::{alert_task, clear_unverified_users_task, update_activity_data_task},
+    startup::{get_upstream_client, Application},
+    tasks::{
+        alert_task, deliver_outbox_task, unverified_users_task, update_activity_data_task,
+        update_forecasts_task,
+    },
     telemetry::init_tracing,
 };
 use tokio::task::JoinError;
@@ -13,14 +16,24 @@ async fn main() -> anyhow::Result<()> {
     let config = get_configuration()?;
     init_tracing("debug");
 
-    let application = Application::build(config.clone())?;
+    // A single client is shared by the API and every task, so that they share the
+    // circuit breakers and cached responses for each provider.
+    let upstream_client = get_upstream_client(&config.upstream)?;
+
+    let application = Application::build(config.clone(), upstream_client.clone())?;
     let sock_addr = application.sock_addr;
     let application_task = tokio::spawn(application.run_until_stopped());
 
     // Background tasks TODO: these should be spawned once globally, rather than every time an application is spun up
-    let alert_worker = tokio::spawn(alert_task(config.clone()));
-    let update_activity_data_worker = tokio::spawn(update_activity_data_task(config.clone()));
-    let clear_unverified_users_worker = tokio::spawn(clear_unverified_users_task(config.clone()));
+    let alert_worker = tokio::spawn(alert_task(config.clone(), upstream_client.clone()));
+    let update_activity_data_worker = tokio::spawn(update_activity_data_task(
+        config.clone(),
+        upstream_client.clone(),
+    ));
+    let unverified_users_worker = tokio::spawn(unverified_users_task(config.clone()));
+    let update_forecasts_worker =
+        tokio::spawn(update_forecasts_task(config.clone(), upstream_client));
+    let deliver_outbox_worker = tokio::spawn(deliver_outbox_task(config.clone()));
 
     tracing::info!("running on http://{sock_addr}");
 
@@ -28,7 +41,9 @@ async fn main() -> anyhow::Result<()> {
         o = application_task => report_exit("API", o),
         o = alert_worker => report_exit("Alert task", o),
         o = update_activity_data_worker => report_exit("Update activity data task", o),
-        o = clear_unverified_users_worker => report_exit("Clear unverified users task", o),
+        o = unverified_users_worker => report_exit("Unverified users task", o),
+        o = update_forecasts_worker => report_exit("Update forecasts task", o),
+        o = deliver_outbox_worker => report_exit("Deliver outbox task", o),
     };
 
     Ok(())
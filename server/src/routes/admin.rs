@@ -0,0 +1,71 @@
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap};
+use axum::routing::{get, Router};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+
+use crate::db;
+use crate::error::Error;
+use crate::startup::{AdminState, AppState, DbState};
+
+pub fn router(app_state: AppState) -> Router<AppState> {
+    Router::with_state(app_state).route("/admin/notifications", get(notifications))
+}
+
+/// Check the request carries the admin bearer token.
+///
+/// The admin API doesn't exist as far as clients are concerned if no token has
+/// been configured.
+fn authorise(headers: &HeaderMap, admin: &AdminState) -> Result<(), Error> {
+    let token = admin.token.as_deref().ok_or(Error::NotFound)?;
+    let provided = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(Error::Unauthorized)?;
+
+    // Compare in constant time, so the token can't be guessed from how long the
+    // comparison takes.
+    let matches = provided.len() == token.len()
+        && provided
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0;
+    if matches {
+        Ok(())
+    } else {
+        Err(Error::Unauthorized)
+    }
+}
+
+/// The maximum number of notifications which can be requested at once.
+const MAX_NOTIFICATIONS: i64 = 1000;
+
+#[derive(Deserialize)]
+struct NotificationsQuery {
+    #[serde(flatten)]
+    filter: db::NotificationFilter,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct NotificationsBody {
+    notifications: Vec<db::Notification>,
+}
+
+/// Return the most recent notifications sent to users, newest first, optionally
+/// filtered by recipient, kind, delivery status and time.
+async fn notifications(
+    headers: HeaderMap,
+    State(admin): State<AdminState>,
+    State(db): State<DbState>,
+    Query(query): Query<NotificationsQuery>,
+) -> Result<Json<NotificationsBody>, Error> {
+    authorise(&headers, &admin)?;
+
+    let limit = query.limit.unwrap_or(100).clamp(1, MAX_NOTIFICATIONS);
+    let notifications = db::get_notifications(&query.filter, limit, &db.pool).await?;
+
+    Ok(Json(NotificationsBody { notifications }))
+}
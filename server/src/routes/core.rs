@@ -7,7 +7,8 @@ use chrono::Timelike;
 use serde::Deserialize;
 use serde::Serialize;
 
-use crate::common::ActivityData;
+use crate::astronomy::MoonIllumination;
+use crate::common::{ActivityData, Aggregation, AlertLevel, Thresholds, DEFAULT_STATION};
 use crate::error::Error;
 use crate::startup::AppState;
 use crate::types::{DateTimeUtc, SanitisedString};
@@ -16,7 +17,11 @@ use crate::{db, startup::DbState};
 pub fn router(app_state: AppState) -> Router<AppState> {
     Router::with_state(app_state)
         .route("/activity", get(activity))
+        .route("/forecast", get(forecast))
         .route("/locations", get(locations))
+        .route("/stations", get(stations))
+        .route("/thresholds", get(thresholds))
+        .route("/visibility", get(visibility))
 }
 
 #[derive(Deserialize)]
@@ -50,9 +55,16 @@ async fn locations(
     Ok(Json(LocationsBody { locations }))
 }
 
+/// The maximum number of data points which can be requested at once.
+const MAX_ACTIVITY_DATA_POINTS: i64 = 10_000;
+
 #[derive(Deserialize)]
 struct ActivityQuery {
+    start: Option<DateTimeUtc>,
     end: Option<DateTimeUtc>,
+    #[serde(default)]
+    aggregation: Aggregation,
+    station: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -61,24 +73,157 @@ struct ActivityBody {
     activity_data: ActivityData,
 }
 
-/// Return the hourly activity data for the 24 hours ending at the given `end`.
+/// Return the activity data between `start` and `end`, as recorded at the given
+/// `station`, aggregated into hourly, daily or weekly data points.
 ///
-/// If `end` is None, then the data for the latest 24 hours is returned.
+/// If `end` is None, then it defaults to the start of the current hour. If
+/// `start` is None, then it defaults to 23 hours before `end`, such that the
+/// hourly data for the latest 24 hours is returned. If `station` is None, then
+/// the data for the default station is returned.
 #[tracing::instrument(name = "Fetch activity data")]
 async fn activity(
-    Query(ActivityQuery { end }): Query<ActivityQuery>,
+    Query(ActivityQuery {
+        start,
+        end,
+        aggregation,
+        station,
+    }): Query<ActivityQuery>,
     State(db): State<DbState>,
 ) -> Result<Json<ActivityBody>, Error> {
     let end = end.unwrap_or_else(|| {
         let now = chrono::Utc::now();
         now.date().and_hms(now.time().hour(), 0, 0)
     });
+    let start = start.unwrap_or_else(|| end - chrono::Duration::hours(23));
+    let station = station.unwrap_or_else(|| DEFAULT_STATION.to_string());
+
+    if start > end {
+        return Err(Error::BadRequest(
+            "`start` must not be after `end`".to_string(),
+        ));
+    }
 
-    let activity_data = db::get_activity_data(&end, &db.pool).await?;
+    let bucket_seconds = aggregation.bucket_duration().num_seconds();
+    if (end - start).num_seconds() / bucket_seconds >= MAX_ACTIVITY_DATA_POINTS {
+        return Err(Error::BadRequest(format!(
+            "at most {MAX_ACTIVITY_DATA_POINTS} data points can be requested at once"
+        )));
+    }
+
+    let activity_data = db::get_activity_data(&start, &end, aggregation, &station, &db.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
 
     Ok(Json(ActivityBody { activity_data }))
 }
 
+#[derive(Serialize)]
+struct StationsBody {
+    stations: Vec<db::Station>,
+}
+
+/// Return all of the stations for which activity data is available.
+#[tracing::instrument(name = "Fetch stations")]
+async fn stations(State(db): State<DbState>) -> Result<Json<StationsBody>, Error> {
+    let stations = db::get_stations(&db.pool).await?;
+    Ok(Json(StationsBody { stations }))
+}
+
+#[derive(Deserialize)]
+struct ThresholdsQuery {
+    station: Option<String>,
+}
+
+/// Return the activity level, in nT, at which each alert level applies for the
+/// given `station`.
+///
+/// If `station` is None, then the thresholds for the default station are
+/// returned.
+#[tracing::instrument(name = "Fetch thresholds")]
+async fn thresholds(
+    Query(ThresholdsQuery { station }): Query<ThresholdsQuery>,
+    State(db): State<DbState>,
+) -> Result<Json<Thresholds>, Error> {
+    let station = station.unwrap_or_else(|| DEFAULT_STATION.to_string());
+
+    let thresholds = db::get_thresholds(&station, &db.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(Json(thresholds))
+}
+
+#[derive(Deserialize)]
+struct ForecastQuery {
+    location_id: i32,
+}
+
+#[derive(Serialize)]
+struct ForecastBody {
+    location_id: i32,
+    forecast: Vec<db::ForecastPoint>,
+}
+
+/// Return the upcoming cloud cover forecast for the given location.
+///
+/// Forecasts are only kept for locations which at least one user has
+/// subscribed to, so the forecast will be empty for any other location.
+#[tracing::instrument(name = "Fetch cloud cover forecast")]
+async fn forecast(
+    Query(ForecastQuery { location_id }): Query<ForecastQuery>,
+    State(db): State<DbState>,
+) -> Result<Json<ForecastBody>, Error> {
+    let now = chrono::Utc::now();
+    let start_of_hour = now.date().and_hms(now.time().hour(), 0, 0);
+
+    let forecast = db::get_cloud_cover_forecasts(&[location_id], &start_of_hour, &db.pool)
+        .await?
+        .remove(&location_id)
+        .unwrap_or_default();
+
+    Ok(Json(ForecastBody {
+        location_id,
+        forecast,
+    }))
+}
+
+#[derive(Deserialize)]
+struct VisibilityQuery {
+    location_id: i32,
+}
+
+#[derive(Serialize)]
+struct VisibilityBody {
+    alert_level: AlertLevel,
+    moon: MoonIllumination,
+    #[serde(flatten)]
+    location: db::Location,
+}
+
+/// Return the current visibility score at the given location, along with the
+/// alert level, weather and sky conditions it was derived from.
+#[tracing::instrument(name = "Fetch visibility score")]
+async fn visibility(
+    Query(VisibilityQuery { location_id }): Query<VisibilityQuery>,
+    State(db): State<DbState>,
+) -> Result<Json<VisibilityBody>, Error> {
+    let mut location = db::get_location(location_id, &db.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let alert_level = db::get_alert_level(&db.pool).await?.alert_level;
+
+    let now = chrono::Utc::now();
+    let moon = MoonIllumination::at(&now);
+    location.attach_sky(&now);
+    location.attach_visibility_score(alert_level, &moon);
+
+    Ok(Json(VisibilityBody {
+        alert_level,
+        moon,
+        location,
+    }))
+}
+
 #[cfg(test)]
 mod tests {
     // use super::*;
@@ -2,12 +2,14 @@ use axum::Router;
 
 use crate::startup::AppState;
 
+mod admin;
 mod core;
 mod users;
 
 pub fn api_router(app_state: AppState) -> Router {
     Router::new()
         .route("/ping", axum::routing::get(|| async { "pong" }))
+        .merge(admin::router(app_state.clone()))
         .merge(core::router(app_state.clone()))
         .merge(users::router(app_state.clone()))
 }
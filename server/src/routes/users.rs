@@ -1,33 +1,153 @@
+use std::collections::HashSet;
+
 use axum::debug_handler;
-use axum::extract::State;
-use axum::routing::{patch, post, Router};
+use axum::extract::{Query, State};
+use axum::routing::{get, patch, post, Router};
 use axum::Json;
+use chrono::NaiveTime;
 use common::ApiResponse;
 use serde::Deserialize;
 use uuid::Uuid;
 
 use crate::db;
+use crate::db::DbPool;
 use crate::error::Error;
-use crate::startup::{AppState, DbState};
+use crate::startup::{AppState, DbState, TokenState};
+use crate::tokens::TokenPurpose;
 
 pub fn router(app_state: AppState) -> Router<AppState> {
     Router::with_state(app_state)
         .route("/users", post(register).delete(unsubscribe))
         .route("/users/verify", patch(verify))
+        .route("/users/verify/resend", post(resend_verification))
+        .route("/users/unsubscribe", post(one_click_unsubscribe))
+        .route("/users/manage", get(settings).post(request_manage_link))
+        .route("/users/preferences", patch(update_preferences))
+        .route("/users/locations", patch(update_locations))
 }
 
+/// The signed token from a link we've emailed to a user, which identifies them.
 #[derive(Deserialize)]
-pub struct VerifyUser {
-    user_id: Uuid,
-    email: String,
+struct TokenParams {
+    token: String,
+}
+
+/// A request body which carries the signed token of the user it applies to.
+#[derive(Deserialize)]
+struct WithToken<T> {
+    token: String,
+    #[serde(flatten)]
+    body: T,
 }
 
+/// A request for an email to be sent to the user with the given address.
 #[derive(Deserialize)]
-struct UnsubscribeUser {
-    user_id: Uuid,
+struct EmailRequest {
     email: String,
 }
 
+#[derive(Deserialize)]
+struct UpdateLocations {
+    locations: Vec<db::RegisterLocation>,
+}
+
+/// The maximum number of locations a user can subscribe to.
+const MAX_LOCATIONS: usize = 5;
+
+/// How long to wait after emailing an address before another email can be
+/// requested for it.
+const EMAIL_REQUEST_COOLDOWN_MINUTES: i64 = 5;
+
+/// Whether an email was sent to the address too recently to send another on
+/// request, which stops the public endpoints being used to flood a mailbox.
+async fn recently_emailed(email: &str, pool: &DbPool) -> Result<bool, Error> {
+    let cooldown_start =
+        chrono::Utc::now() - chrono::Duration::minutes(EMAIL_REQUEST_COOLDOWN_MINUTES);
+    let last_emailed_at = db::get_last_account_email_at(email, pool).await?;
+
+    Ok(last_emailed_at.map_or(false, |last_emailed_at| last_emailed_at > cooldown_start))
+}
+
+/// Check that a percentage, such as a cloud cover, is between 0 and 100.
+fn validate_percentage(name: &str, value: i16) -> Result<(), Error> {
+    if !(0..=100).contains(&value) {
+        return Err(Error::BadRequest(format!(
+            "{name} must be between 0 and 100"
+        )));
+    }
+    Ok(())
+}
+
+/// Check the preferences a user sets when registering, or managing their
+/// subscription.
+fn validate_preferences(
+    min_visibility_score: i16,
+    max_cloud_cover: i16,
+    quiet_hours_start: Option<NaiveTime>,
+    quiet_hours_end: Option<NaiveTime>,
+    alert_cooldown_minutes: i32,
+) -> Result<(), Error> {
+    validate_percentage("min_visibility_score", min_visibility_score)?;
+    validate_percentage("max_cloud_cover", max_cloud_cover)?;
+    if quiet_hours_start.is_some() != quiet_hours_end.is_some() {
+        return Err(Error::BadRequest(
+            "quiet hours need both a start and an end".to_string(),
+        ));
+    }
+    if alert_cooldown_minutes < 0 {
+        return Err(Error::BadRequest(
+            "alert_cooldown_minutes must not be negative".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Check the locations a user subscribes to when registering, or managing their
+/// subscription.
+async fn validate_locations(
+    locations: &[db::RegisterLocation],
+    pool: &DbPool,
+) -> Result<(), Error> {
+    if locations.is_empty() || locations.len() > MAX_LOCATIONS {
+        return Err(Error::BadRequest(format!(
+            "between 1 and {MAX_LOCATIONS} locations are required"
+        )));
+    }
+
+    let location_ids = locations
+        .iter()
+        .map(|location| location.location_id)
+        .collect::<HashSet<_>>();
+    if location_ids.len() != locations.len() {
+        return Err(Error::BadRequest("locations must be unique".to_string()));
+    }
+
+    for location in locations {
+        if let Some(max_cloud_cover) = location.max_cloud_cover {
+            validate_percentage("max_cloud_cover", max_cloud_cover)?;
+        }
+    }
+
+    let location_ids = location_ids.into_iter().collect::<Vec<_>>();
+    let unknown_location_ids = db::get_unknown_location_ids(&location_ids, pool).await?;
+    if !unknown_location_ids.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "unknown locations: {unknown_location_ids:?}"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Check that the token was issued for `purpose`, returning the user it
+/// identifies.
+fn authorise(tokens: &TokenState, token: &str, purpose: TokenPurpose) -> Result<Uuid, Error> {
+    tokens.signer.verify(token, purpose).map_err(|e| {
+        tracing::debug!("rejected {purpose:?} token: {e}");
+        Error::Unauthorized
+    })
+}
+
 /// Register a new user to the Aurora Alert service.
 #[debug_handler]
 async fn register(
@@ -44,56 +164,99 @@ async fn register(
     let pool = app_state.database.pool;
     let email_client = app_state.email.email_client;
 
-    let user = db::get_user_by_email(&user_details, &pool).await?;
+    validate_preferences(
+        user_details.min_visibility_score,
+        user_details.max_cloud_cover,
+        user_details.quiet_hours_start,
+        user_details.quiet_hours_end,
+        user_details.alert_cooldown_minutes,
+    )?;
+    validate_locations(&user_details.locations, &pool).await?;
+
+    let user = db::get_user_by_email(&user_details.email, &pool).await?;
     if let Some(user) = user {
-        let message = email_client
+        email_client
             .new_user_already_registered(&user.email)
             .add_context(&user)?
-            .build_email()?;
-
-        email_client.send(message).await;
+            .enqueue(None, &pool)
+            .await?;
     } else {
-        let user = db::insert_user(&user_details, &pool).await?;
+        // The verification email is enqueued in the same transaction, so that a
+        // user is never registered without one.
+        let mut tx = pool.begin().await?;
+        let user = db::insert_user(&user_details, &mut tx).await?;
 
-        let message = email_client
+        email_client
             .new_verify_user(&user.email)
             .add_context(&user)?
-            .build_email()?;
+            .enqueue(None, &mut tx)
+            .await?;
 
-        email_client.send(message).await;
+        tx.commit().await?;
     };
 
     Ok(Json(ApiResponse::success()))
 }
 
-/// Set the user with the given identifiers as verified.
+/// Set the user identified by a verify token as verified.
 async fn verify(
     State(db): State<DbState>,
-    Json(user_params): Json<VerifyUser>,
+    State(tokens): State<TokenState>,
+    Json(params): Json<TokenParams>,
 ) -> Result<Json<ApiResponse>, Error> {
-    let user_id = db::set_user_verified(&user_params.user_id, &user_params.email, &db.pool).await?;
+    let user_id = authorise(&tokens, &params.token, TokenPurpose::Verify)?;
 
-    if let Some(user_id) = user_id {
+    if let Some(user_id) = db::set_user_verified(&user_id, &db.pool).await? {
         tracing::debug!("user with user_id {user_id:?} verified successfully");
         Ok(Json(ApiResponse::success()))
     } else {
-        tracing::debug!(
-            "failed to verify user with user_id {:?}",
-            user_params.user_id
-        );
+        tracing::debug!("failed to verify user with user_id {user_id:?}");
         Err(Error::NotFound)
     }
 }
 
-/// Unsubscribe the user with the given identifiers from the Aurora Alert
-/// service.
+/// Resend the verification email to the user with the given email address, if
+/// they haven't verified yet.
+///
+/// Requests made too soon after the last email are ignored. The response is
+/// the same in every case, so that this can't be used to find out who is
+/// subscribed.
+async fn resend_verification(
+    State(app_state): State<AppState>,
+    Json(request): Json<EmailRequest>,
+) -> Result<Json<ApiResponse>, Error> {
+    let pool = app_state.database.pool;
+    let email_client = app_state.email.email_client;
+
+    match db::get_user_by_email(&request.email, &pool).await? {
+        Some(user) if !user.verified => {
+            if recently_emailed(&user.email, &pool).await? {
+                tracing::debug!("verification resend requested too soon after the last email");
+            } else {
+                email_client
+                    .new_verify_user(&user.email)
+                    .add_context(&user)?
+                    .enqueue(None, &pool)
+                    .await?;
+            }
+        }
+        Some(_) => tracing::debug!("verification resend requested for a verified user"),
+        None => tracing::debug!("verification resend requested for an unknown user"),
+    }
+
+    Ok(Json(ApiResponse::success()))
+}
+
+/// Unsubscribe the user identified by an unsubscribe token from the Aurora
+/// Alert service.
 async fn unsubscribe(
     State(db): State<DbState>,
-    Json(user): Json<UnsubscribeUser>,
+    State(tokens): State<TokenState>,
+    Json(params): Json<TokenParams>,
 ) -> Result<Json<ApiResponse>, Error> {
-    let user_id = db::delete_user(&user.user_id, &user.email, &db.pool).await?;
+    let user_id = authorise(&tokens, &params.token, TokenPurpose::Unsubscribe)?;
 
-    if let Some(user_id) = user_id {
+    if let Some(user_id) = db::delete_user(&user_id, &db.pool).await? {
         tracing::debug!("user with user_id {user_id:?} deleted succesfully");
         Ok(Json(ApiResponse::success()))
     } else {
@@ -101,3 +264,111 @@ async fn unsubscribe(
         Err(Error::NotFound)
     }
 }
+
+/// Unsubscribe a user by POSTing to the URL in the `List-Unsubscribe` header of
+/// an alert email, as described in RFC 8058.
+///
+/// The token is in the query string, and the body, which should be
+/// `List-Unsubscribe=One-Click`, is ignored, so that this works from mail
+/// clients as well as plain HTML forms.
+async fn one_click_unsubscribe(
+    State(db): State<DbState>,
+    State(tokens): State<TokenState>,
+    Query(params): Query<TokenParams>,
+) -> Result<Json<ApiResponse>, Error> {
+    unsubscribe(State(db), State(tokens), Json(params)).await
+}
+
+/// Email a link to manage their subscription to the user with the given email
+/// address, if there is one.
+///
+/// Requests made too soon after the last email are ignored. The response is
+/// the same in every case, so that this can't be used to find out who is
+/// subscribed.
+async fn request_manage_link(
+    State(app_state): State<AppState>,
+    Json(request): Json<EmailRequest>,
+) -> Result<Json<ApiResponse>, Error> {
+    let pool = app_state.database.pool;
+    let email_client = app_state.email.email_client;
+
+    match db::get_user_by_email(&request.email, &pool).await? {
+        Some(user) => {
+            if recently_emailed(&user.email, &pool).await? {
+                tracing::debug!("manage link requested too soon after the last email");
+            } else {
+                email_client
+                    .new_manage_subscription(&user.email)
+                    .add_context(&user)?
+                    .enqueue(None, &pool)
+                    .await?;
+            }
+        }
+        None => tracing::debug!("manage link requested for an unknown user"),
+    }
+
+    Ok(Json(ApiResponse::success()))
+}
+
+/// Return the settings of the user identified by a manage token.
+async fn settings(
+    State(db): State<DbState>,
+    State(tokens): State<TokenState>,
+    Query(params): Query<TokenParams>,
+) -> Result<Json<db::UserWithLocations>, Error> {
+    let user_id = authorise(&tokens, &params.token, TokenPurpose::Manage)?;
+
+    let user = db::get_user(&user_id, &db.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(Json(user))
+}
+
+/// Change the preferences of the user identified by a manage token.
+async fn update_preferences(
+    State(db): State<DbState>,
+    State(tokens): State<TokenState>,
+    Json(request): Json<WithToken<db::UpdatePreferences>>,
+) -> Result<Json<ApiResponse>, Error> {
+    let user_id = authorise(&tokens, &request.token, TokenPurpose::Manage)?;
+    let preferences = request.body;
+
+    validate_preferences(
+        preferences.min_visibility_score,
+        preferences.max_cloud_cover,
+        preferences.quiet_hours_start,
+        preferences.quiet_hours_end,
+        preferences.alert_cooldown_minutes,
+    )?;
+
+    if let Some(user_id) = db::update_user_preferences(&user_id, &preferences, &db.pool).await? {
+        tracing::debug!("user with user_id {user_id:?} updated their preferences");
+        Ok(Json(ApiResponse::success()))
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// Replace the locations of the user identified by a manage token.
+async fn update_locations(
+    State(db): State<DbState>,
+    State(tokens): State<TokenState>,
+    Json(request): Json<WithToken<UpdateLocations>>,
+) -> Result<Json<ApiResponse>, Error> {
+    let user_id = authorise(&tokens, &request.token, TokenPurpose::Manage)?;
+    let locations = request.body.locations;
+
+    validate_locations(&locations, &db.pool).await?;
+
+    let mut tx = db.pool.begin().await?;
+    let user_id = db::replace_user_locations(&user_id, &locations, &mut tx).await?;
+    tx.commit().await?;
+
+    if let Some(user_id) = user_id {
+        tracing::debug!("user with user_id {user_id:?} updated their locations");
+        Ok(Json(ApiResponse::success()))
+    } else {
+        Err(Error::NotFound)
+    }
+}
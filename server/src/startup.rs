@@ -13,15 +13,37 @@ use sqlx::{
 use tower_http::trace::TraceLayer;
 
 use crate::{
-    configuration::{DatabaseSettings, EmailSettings, Settings},
-    email::EmailClient,
+    apis::UpstreamClient,
+    configuration::{DatabaseSettings, Settings, TokenSettings, UpstreamSettings},
+    email::{EmailClient, EmailError},
     routes::api_router,
+    tokens::TokenSigner,
 };
 
 #[derive(Clone, Debug)]
 pub struct AppState {
+    pub admin: AdminState,
     pub database: DbState,
     pub email: EmailState,
+    pub tokens: TokenState,
+    pub upstream: UpstreamState,
+}
+
+#[derive(Clone)]
+pub struct AdminState {
+    pub token: Option<String>,
+}
+
+impl std::fmt::Debug for AdminState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AdminState").finish_non_exhaustive()
+    }
+}
+
+impl FromRef<AppState> for AdminState {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.admin.clone()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -46,19 +68,51 @@ impl FromRef<AppState> for EmailState {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct TokenState {
+    pub signer: TokenSigner,
+}
+
+impl FromRef<AppState> for TokenState {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.tokens.clone()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UpstreamState {
+    pub client: UpstreamClient,
+}
+
+impl FromRef<AppState> for UpstreamState {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.upstream.clone()
+    }
+}
+
 pub struct Application {
     app: Router,
     pub sock_addr: SocketAddr,
 }
 
 impl Application {
-    pub fn build(config: Settings) -> Result<Self, anyhow::Error> {
+    pub fn build(config: Settings, upstream_client: UpstreamClient) -> Result<Self, anyhow::Error> {
         let pool = get_connection_pool(&config.database);
-        let email_client = get_email_client(&config.email);
+        let token_signer = get_token_signer(&config.tokens)?;
+        let email_client = get_email_client(&config, token_signer.clone())?;
 
         let app_state = AppState {
+            admin: AdminState {
+                token: config.application.admin_token.clone(),
+            },
             database: DbState { pool },
             email: EmailState { email_client },
+            tokens: TokenState {
+                signer: token_signer,
+            },
+            upstream: UpstreamState {
+                client: upstream_client,
+            },
         };
 
         let app = Router::new()
@@ -98,6 +152,19 @@ pub fn get_connection_pool(config: &DatabaseSettings) -> PgPool {
         })
 }
 
-pub fn get_email_client(config: &EmailSettings) -> EmailClient {
-    EmailClient::new(&config)
+pub fn get_email_client(config: &Settings, signer: TokenSigner) -> Result<EmailClient, EmailError> {
+    EmailClient::new(
+        &config.email,
+        &config.application.base_url,
+        signer,
+        config.verification.window(),
+    )
+}
+
+pub fn get_token_signer(config: &TokenSettings) -> Result<TokenSigner, anyhow::Error> {
+    TokenSigner::new(config)
+}
+
+pub fn get_upstream_client(config: &UpstreamSettings) -> Result<UpstreamClient, reqwest::Error> {
+    UpstreamClient::new(config)
 }
@@ -1,143 +1,501 @@
+use std::collections::HashMap;
+
 use crate::apis;
+use crate::apis::geomagnetic::{GeomagneticSource, GeomagneticSources};
+use crate::apis::weather::WeatherProvider;
+use crate::apis::UpstreamClient;
+use crate::astronomy::MoonIllumination;
 use crate::common::AlertLevel;
-use crate::configuration::Settings;
+use crate::configuration::{OutboxSettings, Settings, VerificationSettings};
 use crate::db;
 use crate::db::DbPool;
 use crate::email::EmailClient;
 use crate::helpers;
-use crate::startup::{get_connection_pool, get_email_client};
+use crate::startup::{get_connection_pool, get_email_client, get_token_signer};
+use crate::types::DateTimeUtc;
+
+/// How often, in polls, to log how many polls were skipped.
+const POLL_STATS_LOG_INTERVAL: u64 = 12;
+
+/// Whether a poll of an upstream API found anything new to process.
+#[derive(Debug, PartialEq, Eq)]
+enum PollOutcome {
+    Processed,
+    Unchanged,
+}
+
+/// Counts of how many polls of an upstream API were skipped because nothing had
+/// changed since the last one.
+#[derive(Debug)]
+struct PollStats {
+    task: &'static str,
+    polls: u64,
+    skipped: u64,
+}
+
+impl PollStats {
+    fn new(task: &'static str) -> Self {
+        Self {
+            task,
+            polls: 0,
+            skipped: 0,
+        }
+    }
+
+    fn record(&mut self, outcome: PollOutcome) {
+        self.polls += 1;
+        if outcome == PollOutcome::Unchanged {
+            self.skipped += 1;
+        }
 
-/// Send an email alert to all users where the alert criteria are met.
+        if self.polls.is_multiple_of(POLL_STATS_LOG_INTERVAL) {
+            tracing::info!(
+                "{}: {} of {} polls skipped as the upstream data was unchanged",
+                self.task,
+                self.skipped,
+                self.polls
+            );
+        }
+    }
+}
+
+/// Send an email alert to all users where the alert criteria are met, and an all
+/// clear to those who were alerted once activity has subsided.
 async fn maybe_alert(
-    open_weather_api_key: &str,
+    weather_provider: &dyn WeatherProvider,
+    geomagnetic_source: &dyn GeomagneticSource,
     pool: &DbPool,
     email_client: &EmailClient,
-) -> Result<(), anyhow::Error> {
+) -> Result<PollOutcome, anyhow::Error> {
     let stored_alert_level = db::get_alert_level(pool).await?;
-    let live_alert_level = apis::aurora_watch::get_alert_level().await?;
+    let live_alert_level = geomagnetic_source.current_alert_level().await?;
 
-    if (stored_alert_level.updated_at == live_alert_level.updated_at)
+    let same_site = stored_alert_level.site_id == live_alert_level.site_id;
+
+    if same_site
+        && (stored_alert_level.updated_at == live_alert_level.updated_at)
         && (stored_alert_level.alert_level == AlertLevel::Green)
     {
         // The live alert level is the same as the stored alert level. If the alert
         // level is yellow or higher, we still need to check for alerts because the
         // cloud cover may have reduced.
-        return Ok(());
+        return Ok(PollOutcome::Unchanged);
     }
 
-    if live_alert_level.updated_at > stored_alert_level.updated_at {
-        // The live alert level is more up to date than the stored alert level,
-        // so we need to update the stored alert level.
+    if !same_site || live_alert_level.updated_at > stored_alert_level.updated_at {
+        // The live alert level is more up to date than the stored alert level, or
+        // has come from a different site (e.g. because of a fallback to another
+        // source), so we need to update the stored alert level.
         db::update_alert_level(&live_alert_level, pool).await?;
     }
 
+    let now = chrono::Utc::now();
+    let mut forecasts = HashMap::new();
     if live_alert_level.level >= AlertLevel::Yellow {
         // We are at yellow or above: update the weather reports if they are stale.
         let locations = db::get_unique_user_locations(pool).await?;
-        let now = chrono::Utc::now();
         let four_minutes_ago = now - chrono::Duration::minutes(4);
         for location in &locations {
             if location.updated_at < four_minutes_ago {
-                let live_weather =
-                    apis::open_weather::get_weather(location.location_id, &open_weather_api_key)
-                        .await?;
+                // A failure for one location shouldn't stop the others being updated, or
+                // alerts being sent out using the last known weather.
+                let live_weather = match weather_provider
+                    .current_weather(&location.weather_location())
+                    .await
+                {
+                    Ok(live_weather) => live_weather,
+                    Err(e) => {
+                        tracing::error!(
+                            "error fetching weather for location {} from {}: {e}",
+                            location.location_id,
+                            weather_provider.name()
+                        );
+                        continue;
+                    }
+                };
 
-                db::update_weather(live_weather, location.location_id, pool).await?;
+                if let Err(e) = db::update_weather(live_weather, location.location_id, pool).await {
+                    tracing::error!(
+                        "error updating weather for location {} in database: {e}",
+                        location.location_id
+                    );
+                }
             }
         }
 
-        // Send out alerts to verified users, if they are due one.
-        let verified_users = db::get_verified_users(pool).await?;
-        for user in &verified_users {
-            if helpers::should_alert_user(user, &live_alert_level.level) {
+        let location_ids = locations
+            .iter()
+            .map(|location| location.location_id)
+            .collect::<Vec<_>>();
+        forecasts = db::get_cloud_cover_forecasts(&location_ids, &now, pool).await?;
+    }
+
+    // Send out alerts, or all clears, to verified users, if they are due one.
+    let moon = MoonIllumination::at(&now);
+    let mut verified_users = db::get_verified_users(pool).await?;
+    for user in &mut verified_users {
+        if helpers::is_all_clear(user, &live_alert_level.level) {
+            // There's no point waking anyone up to tell them it's over, so the all
+            // clear is dropped during quiet hours.
+            let mut tx = pool.begin().await?;
+            if user.notify_all_clear && !helpers::in_quiet_hours(user, &now) {
                 let message = email_client
-                    .new_alert(&user.email)
-                    .add_context(user, &live_alert_level.level)?
-                    .build_email()?;
+                    .new_all_clear(&user.email)
+                    .add_context(user, &live_alert_level.level)?;
+                let notification_id = db::insert_notification(
+                    user,
+                    db::NotificationKind::AllClear,
+                    &live_alert_level,
+                    &mut tx,
+                )
+                .await?;
+                message.enqueue(Some(notification_id), &mut tx).await?;
+            }
 
-                email_client.send(message).await;
+            db::clear_user_last_notified_level(&user.user_id, &mut tx).await?;
+            tx.commit().await?;
+            continue;
+        }
 
-                db::update_user_last_alerted_at(&user.user_id, pool).await?;
-            }
+        if let Some(alert_level) = helpers::lowered_notified_level(user, &live_alert_level.level) {
+            db::update_user_last_notified_level(&user.user_id, alert_level, pool).await?;
+            user.last_notified_level = Some(alert_level);
+        }
+
+        if live_alert_level.level < AlertLevel::Yellow {
+            continue;
+        }
+
+        user.attach_skies(&now);
+        user.attach_visibility_scores(live_alert_level.level, &moon);
+        if helpers::should_alert_user(user, &live_alert_level.level) {
+            user.attach_forecasts(&forecasts);
+            let message = email_client
+                .new_alert(&user.email)
+                .add_context(user, &live_alert_level.level)?;
+
+            // The user is only marked as alerted if the alert is safely in the
+            // outbox, from which it will be retried until it's delivered.
+            let mut tx = pool.begin().await?;
+            let notification_id = db::insert_notification(
+                user,
+                db::NotificationKind::Alert,
+                &live_alert_level,
+                &mut tx,
+            )
+            .await?;
+            message.enqueue(Some(notification_id), &mut tx).await?;
+            db::update_user_last_notified(&user.user_id, live_alert_level.level, &mut tx).await?;
+            tx.commit().await?;
         }
     }
 
-    Ok(())
+    Ok(PollOutcome::Processed)
 }
 
 /// A task which runs every 5 minutes and conditionally sends out email notifications of alert level changes.
-pub async fn alert_task(config: Settings) -> Result<(), anyhow::Error> {
+pub async fn alert_task(
+    config: Settings,
+    upstream_client: UpstreamClient,
+) -> Result<(), anyhow::Error> {
     tracing::debug!("Started alert_task");
     let pool = get_connection_pool(&config.database);
-    let email_client = get_email_client(&config.email);
+    let token_signer = get_token_signer(&config.tokens)?;
+    let email_client = get_email_client(&config, token_signer)?;
+    let geomagnetic_sources = GeomagneticSources::from_settings(&upstream_client);
+    let weather_provider = apis::weather::from_settings(&config.application, &upstream_client)?;
+    let mut poll_stats = PollStats::new("alert_task");
 
     let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 5));
     loop {
         interval.tick().await;
-        if let Err(e) = maybe_alert(
-            &config.application.open_weather_api_key,
+        match maybe_alert(
+            weather_provider.as_ref(),
+            &geomagnetic_sources,
             &pool,
             &email_client,
         )
         .await
         {
-            tracing::error!("error within alert task: {e}");
+            Ok(outcome) => poll_stats.record(outcome),
+            Err(e) => tracing::error!("error within alert task: {e}"),
+        }
+    }
+}
+
+/// How long an email claimed from the outbox is left before it's assumed that
+/// its delivery was interrupted, and it's tried again.
+const OUTBOX_LEASE_MINUTES: i64 = 5;
+
+/// Deliver the emails which are due from the outbox, rescheduling or
+/// dead-lettering those which fail.
+async fn deliver_outbox_emails(
+    settings: &OutboxSettings,
+    pool: &DbPool,
+    email_client: &EmailClient,
+) -> Result<(), anyhow::Error> {
+    let emails = db::claim_outbox_emails(
+        settings.batch_size,
+        chrono::Duration::minutes(OUTBOX_LEASE_MINUTES),
+        pool,
+    )
+    .await?;
+
+    for email in &emails {
+        match email_client.deliver(email).await {
+            Ok(()) => {
+                tracing::debug!("email {} sent to {}", email.email_id, email.to_address);
+                db::mark_email_sent(&email.email_id, pool).await?;
+            }
+            Err(e) => {
+                let attempts = email.attempts + 1;
+                let retry_at = if e.is_permanent() || settings.is_dead_letter(attempts) {
+                    tracing::error!(
+                        "dead-lettering email {} to {} after {attempts} attempts: {e}",
+                        email.email_id,
+                        email.to_address
+                    );
+                    None
+                } else {
+                    tracing::warn!(
+                        "error sending email {} to {}, on attempt {attempts}: {e}",
+                        email.email_id,
+                        email.to_address
+                    );
+                    Some(chrono::Utc::now() + settings.backoff(attempts))
+                };
+                db::record_email_failure(&email.email_id, &e.to_string(), retry_at, pool).await?;
+            }
         }
     }
+
+    Ok(())
 }
 
-/// A task which runs every midnight and deletes any users who remain unverified.
-pub async fn clear_unverified_users_task(config: Settings) -> Result<(), anyhow::Error> {
-    tracing::debug!("started clear_unverified_users_task");
+/// A task which regularly delivers the emails waiting in the outbox.
+pub async fn deliver_outbox_task(config: Settings) -> Result<(), anyhow::Error> {
+    tracing::debug!("Started deliver_outbox_task");
     let pool = get_connection_pool(&config.database);
+    let token_signer = get_token_signer(&config.tokens)?;
+    let email_client = get_email_client(&config, token_signer)?;
+    let settings = config.email.outbox;
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        settings.poll_interval_seconds,
+    ));
     loop {
-        let now = chrono::Utc::now();
-        let tomorrow_midnight = now.date().succ().and_hms(0, 0, 0);
-        let sleep_duration = tomorrow_midnight - now;
+        interval.tick().await;
+        if let Err(e) = deliver_outbox_emails(&settings, &pool, &email_client).await {
+            tracing::error!("error within deliver outbox task: {e}");
+        }
+    }
+}
 
-        tracing::info!(
-            r#"Next run of the "clear_unverified_users" task in ~{:?} hours"#,
-            sleep_duration.num_hours(),
+/// Fill in the coordinates of a location which doesn't have any yet, from the
+/// current weather report, which also stores them.
+async fn backfill_coordinates(
+    weather_provider: &dyn WeatherProvider,
+    location: &mut db::LocationUpdatedAt,
+    pool: &DbPool,
+) {
+    let weather = match weather_provider
+        .current_weather(&location.weather_location())
+        .await
+    {
+        Ok(weather) => weather,
+        Err(e) => {
+            tracing::warn!(
+                "error fetching coordinates for location {} from {}: {e}",
+                location.location_id,
+                weather_provider.name()
+            );
+            return;
+        }
+    };
+
+    let (latitude, longitude) = (weather.latitude, weather.longitude);
+    if let Err(e) = db::update_weather(weather, location.location_id, pool).await {
+        tracing::error!(
+            "error updating weather for location {} in database: {e}",
+            location.location_id
         );
+        return;
+    }
+    location.latitude = location.latitude.or(latitude);
+    location.longitude = location.longitude.or(longitude);
+}
 
-        tokio::time::sleep_until(tokio::time::Instant::now() + sleep_duration.to_std().unwrap())
-            .await;
+/// A task which runs every hour and updates the cloud cover forecasts for every
+/// location associated with a user.
+pub async fn update_forecasts_task(
+    config: Settings,
+    upstream_client: UpstreamClient,
+) -> Result<(), anyhow::Error> {
+    tracing::debug!("started update_forecasts_task");
+    let pool = get_connection_pool(&config.database);
+    let weather_provider = apis::weather::from_settings(&config.application, &upstream_client)?;
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        let mut locations = match db::get_unique_user_locations(&pool).await {
+            Ok(locations) => locations,
+            Err(e) => {
+                tracing::error!("error fetching user locations from database: {e}");
+                continue;
+            }
+        };
 
-        let deleted_users_count = db::delete_unverified_users(&pool).await;
+        for location in &mut locations {
+            if location.latitude.is_none() || location.longitude.is_none() {
+                backfill_coordinates(weather_provider.as_ref(), location, &pool).await;
+            }
 
-        match deleted_users_count {
-            Ok(count) => {
-                tracing::info!(
-                    "{} user(s) deleted from the database as part of daily maintenance",
-                    count
-                )
+            let forecast = match weather_provider
+                .cloud_cover_forecast(&location.weather_location())
+                .await
+            {
+                Ok(forecast) => forecast,
+                Err(e) => {
+                    tracing::error!(
+                        "error fetching forecast for location {} from {}: {e}",
+                        location.location_id,
+                        weather_provider.name()
+                    );
+                    continue;
+                }
+            };
+
+            if let Err(e) =
+                db::update_cloud_cover_forecast(&forecast, location.location_id, &pool).await
+            {
+                tracing::error!(
+                    "error updating forecast for location {} in database: {e}",
+                    location.location_id
+                );
             }
-            Err(e) => tracing::error!("error removing stale unverified users: {e}"),
+        }
+    }
+}
+
+/// Email a reminder to an unverified user, recording that they've been
+/// reminded in the same transaction.
+async fn remind_unverified_user(
+    user: &db::UserWithLocations,
+    pool: &DbPool,
+    email_client: &EmailClient,
+) -> Result<(), anyhow::Error> {
+    let mut tx = pool.begin().await?;
+    email_client
+        .new_verification_reminder(&user.email)
+        .add_context(user)?
+        .enqueue(None, &mut tx)
+        .await?;
+    db::set_verification_reminder_sent(&user.user_id, &mut tx).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Delete the users whose verification window has passed, and remind those
+/// whose window is about to pass.
+async fn process_unverified_users(
+    settings: &VerificationSettings,
+    pool: &DbPool,
+    email_client: &EmailClient,
+) -> Result<(), anyhow::Error> {
+    let expired_before = chrono::Utc::now() - settings.window();
+
+    let deleted_users_count = db::delete_unverified_users(&expired_before, pool).await?;
+    if deleted_users_count > 0 {
+        tracing::info!(
+            "{deleted_users_count} user(s) deleted from the database after failing to verify"
+        );
+    }
+
+    let remind_before = expired_before + settings.reminder();
+    let users = db::get_users_to_remind(&remind_before, &expired_before, pool).await?;
+    for user in &users {
+        match remind_unverified_user(user, pool, email_client).await {
+            Ok(()) => tracing::debug!("reminded user {} to verify", user.user_id),
+            Err(e) => tracing::error!("error reminding user {} to verify: {e}", user.user_id),
+        }
+    }
+
+    Ok(())
+}
+
+/// A task which regularly deletes the users who haven't verified within the
+/// verification window after registering, having reminded them beforehand.
+pub async fn unverified_users_task(config: Settings) -> Result<(), anyhow::Error> {
+    tracing::debug!("started unverified_users_task");
+    let pool = get_connection_pool(&config.database);
+    let token_signer = get_token_signer(&config.tokens)?;
+    let email_client = get_email_client(&config, token_signer)?;
+    let settings = config.verification;
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        settings.poll_interval_seconds,
+    ));
+    loop {
+        interval.tick().await;
+        if let Err(e) = process_unverified_users(&settings, &pool, &email_client).await {
+            tracing::error!("error processing unverified users: {e}");
         }
     }
 }
 
 /// A task which periodically updates the locally cached aurora activity data.
-pub async fn update_activity_data_task(config: Settings) -> Result<(), anyhow::Error> {
+pub async fn update_activity_data_task(
+    config: Settings,
+    upstream_client: UpstreamClient,
+) -> Result<(), anyhow::Error> {
     tracing::debug!("started update_activity_data_task");
     let pool = get_connection_pool(&config.database);
+    let geomagnetic_sources = GeomagneticSources::from_settings(&upstream_client);
+    let mut poll_stats = PollStats::new("update_activity_data_task");
+
+    // The upstream creation time of the latest stored activity data, per station.
+    let mut last_updated: HashMap<String, DateTimeUtc> = match db::get_stations(&pool).await {
+        Ok(stations) => stations
+            .into_iter()
+            .map(|station| (station.station, station.updated_at))
+            .collect(),
+        Err(e) => {
+            tracing::error!("error fetching stations from database: {e}");
+            HashMap::new()
+        }
+    };
+
     let mut interval = tokio::time::interval(std::time::Duration::from_secs(60 * 5));
     loop {
         interval.tick().await;
-        let activity = apis::aurora_watch::get_activity_data().await;
+        let activities = geomagnetic_sources.all_histories().await;
 
-        let activity = match activity {
-            Ok(act) => act,
-            Err(e) => {
-                tracing::error!("Error fetching aurora activity levels: {e}");
+        for activity in activities {
+            let station = activity.station.clone();
+            let updated_at = activity.updated_at;
+
+            if last_updated.get(&station) == Some(&updated_at) {
+                tracing::debug!(
+                    "activity data for station {station} unchanged since {updated_at}, skipping"
+                );
+                poll_stats.record(PollOutcome::Unchanged);
                 continue;
             }
-        };
 
-        let res = db::update_aurora_activity(activity, &pool).await;
+            let res = db::update_aurora_activity(activity, &pool).await;
 
-        if let Err(e) = res {
-            tracing::error!("error updating activity data in database: {e}");
-        };
+            match res {
+                Ok(()) => {
+                    last_updated.insert(station, updated_at);
+                }
+                Err(e) => {
+                    tracing::error!(
+                        "error updating activity data for station {station} in database: {e}"
+                    );
+                }
+            };
+            poll_stats.record(PollOutcome::Processed);
+        }
     }
 }
@@ -1,4 +1,7 @@
+use std::collections::HashMap;
+
 use derive_more::Display;
+use tera::Value;
 pub use tera::{Context, Tera};
 
 pub type TemplateEngine = Tera;
@@ -11,34 +14,98 @@ pub fn init() -> Result<TemplateEngine, tera::Error> {
         .add_raw_templates(vec![
             ("base.html", include_str!("./base.html")),
             ("alert.html", include_str!("./alert.html")),
+            ("all_clear.html", include_str!("./all_clear.html")),
             ("verify.html", include_str!("./verify.html")),
+            ("manage.html", include_str!("./manage.html")),
             (
                 "already_registered.html",
                 include_str!("./already_registered.html"),
             ),
-            ("base.html", include_str!("./base.html")),
+            ("base.txt", include_str!("./base.txt")),
+            ("alert_levels.txt", include_str!("./alert_levels.txt")),
+            ("alert.txt", include_str!("./alert.txt")),
+            ("all_clear.txt", include_str!("./all_clear.txt")),
+            ("verify.txt", include_str!("./verify.txt")),
+            ("manage.txt", include_str!("./manage.txt")),
+            (
+                "already_registered.txt",
+                include_str!("./already_registered.txt"),
+            ),
         ])
         .expect("failed to load templates");
+    engine.register_filter("time", format_time);
 
     Ok(engine)
 }
 
+/// Format a serialised `DateTimeUtc`, using the optional `format` argument, or
+/// "%H:%M" by default, in the timezone named by the optional `tz` argument, or
+/// UTC by default.
+///
+/// Tera's built in `date` filter isn't available without its chrono feature.
+fn format_time(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let timestamp = tera::from_value::<String>(value.clone())?;
+    let timestamp = chrono::DateTime::parse_from_rfc3339(&timestamp)
+        .map_err(|e| tera::Error::msg(format!("error parsing timestamp {timestamp}: {e}")))?;
+    let format = match args.get("format") {
+        Some(format) => tera::from_value::<String>(format.clone())?,
+        None => "%H:%M".to_string(),
+    };
+    let timezone = match args.get("tz") {
+        Some(timezone) => tera::from_value::<String>(timezone.clone())?
+            .parse::<chrono_tz::Tz>()
+            .map_err(|e| tera::Error::msg(format!("error parsing timezone: {e}")))?,
+        None => chrono_tz::UTC,
+    };
+
+    Ok(tera::to_value(
+        timestamp
+            .with_timezone(&timezone)
+            .format(&format)
+            .to_string(),
+    )?)
+}
+
 #[derive(Display)]
 pub enum Template {
     #[display(fmt = "alert.html")]
     Alert,
+    #[display(fmt = "all_clear.html")]
+    AllClear,
     #[display(fmt = "verify.html")]
     VerifyUser,
     #[display(fmt = "already_registered.html")]
     UserAlreadyRegistered,
+    #[display(fmt = "manage.html")]
+    ManageSubscription,
+}
+
+/// An email body, rendered as both HTML and a plain text alternative.
+pub struct RenderedTemplate {
+    pub html: String,
+    pub text: String,
 }
 
 impl Template {
+    /// The name of the plain text version of the template.
+    fn text_name(&self) -> &'static str {
+        match self {
+            Self::Alert => "alert.txt",
+            Self::AllClear => "all_clear.txt",
+            Self::VerifyUser => "verify.txt",
+            Self::UserAlreadyRegistered => "already_registered.txt",
+            Self::ManageSubscription => "manage.txt",
+        }
+    }
+
     pub fn render(
         &self,
         context: &Context,
         template_engine: &TemplateEngine,
-    ) -> Result<String, anyhow::Error> {
-        Ok(template_engine.render(&self.to_string(), context)?)
+    ) -> Result<RenderedTemplate, anyhow::Error> {
+        Ok(RenderedTemplate {
+            html: template_engine.render(&self.to_string(), context)?,
+            text: template_engine.render(self.text_name(), context)?,
+        })
     }
 }
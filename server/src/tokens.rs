@@ -0,0 +1,226 @@
+//! Signed, expiring tokens which identify a user in the links we email them.
+//!
+//! A token is the base64 encoded JSON claims, followed by a `.` and the base64
+//! encoded HMAC-SHA256 of the encoded claims. Each token is scoped to a single
+//! purpose, so that e.g. the unsubscribe link in an alert can't be used to
+//! change a user's settings.
+
+use std::sync::Arc;
+
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use uuid::Uuid;
+
+use crate::configuration::TokenSettings;
+use crate::types::DateTimeUtc;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// The shortest secret we accept, which is the output size of SHA-256.
+const MIN_SECRET_LENGTH: usize = 32;
+
+/// What a token can be used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenPurpose {
+    Verify,
+    Unsubscribe,
+    Manage,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    purpose: TokenPurpose,
+    user_id: Uuid,
+    /// Seconds since the Unix epoch.
+    expires_at: i64,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TokenError {
+    #[error("malformed token")]
+    Malformed,
+
+    #[error("invalid token signature")]
+    InvalidSignature,
+
+    #[error("token was issued for a different purpose")]
+    WrongPurpose,
+
+    #[error("token has expired")]
+    Expired,
+}
+
+/// Issues and validates tokens.
+#[derive(Clone)]
+pub struct TokenSigner {
+    secret: Arc<[u8]>,
+    verify_ttl: Duration,
+    unsubscribe_ttl: Duration,
+    manage_ttl: Duration,
+}
+
+impl std::fmt::Debug for TokenSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenSigner")
+            .field("verify_ttl", &self.verify_ttl)
+            .field("unsubscribe_ttl", &self.unsubscribe_ttl)
+            .field("manage_ttl", &self.manage_ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TokenSigner {
+    pub fn new(config: &TokenSettings) -> Result<Self, anyhow::Error> {
+        anyhow::ensure!(
+            config.secret.len() >= MIN_SECRET_LENGTH,
+            "the token secret must be at least {MIN_SECRET_LENGTH} bytes long"
+        );
+
+        Ok(Self {
+            secret: config.secret.as_bytes().into(),
+            verify_ttl: Duration::hours(config.verify_ttl_hours),
+            unsubscribe_ttl: Duration::hours(config.unsubscribe_ttl_hours),
+            manage_ttl: Duration::hours(config.manage_ttl_hours),
+        })
+    }
+
+    /// How long a token for the given purpose is valid for.
+    fn ttl(&self, purpose: TokenPurpose) -> Duration {
+        match purpose {
+            TokenPurpose::Verify => self.verify_ttl,
+            TokenPurpose::Unsubscribe => self.unsubscribe_ttl,
+            TokenPurpose::Manage => self.manage_ttl,
+        }
+    }
+
+    fn mac(&self, encoded_claims: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(encoded_claims.as_bytes());
+        mac
+    }
+
+    /// Issue a token allowing the given user to do `purpose`.
+    pub fn sign(&self, purpose: TokenPurpose, user_id: &Uuid) -> String {
+        self.sign_at(purpose, user_id, Utc::now())
+    }
+
+    fn sign_at(&self, purpose: TokenPurpose, user_id: &Uuid, now: DateTimeUtc) -> String {
+        let claims = Claims {
+            purpose,
+            user_id: *user_id,
+            expires_at: (now + self.ttl(purpose)).timestamp(),
+        };
+        let claims = serde_json::to_vec(&claims).expect("claims are always serialisable");
+        let claims = base64::encode_config(claims, base64::URL_SAFE_NO_PAD);
+        let signature = base64::encode_config(
+            self.mac(&claims).finalize().into_bytes(),
+            base64::URL_SAFE_NO_PAD,
+        );
+
+        format!("{claims}.{signature}")
+    }
+
+    /// Check that the token was issued by us for `purpose` and hasn't expired,
+    /// returning the user it was issued to.
+    pub fn verify(&self, token: &str, purpose: TokenPurpose) -> Result<Uuid, TokenError> {
+        self.verify_at(token, purpose, Utc::now())
+    }
+
+    fn verify_at(
+        &self,
+        token: &str,
+        purpose: TokenPurpose,
+        now: DateTimeUtc,
+    ) -> Result<Uuid, TokenError> {
+        let (encoded_claims, signature) = token.split_once('.').ok_or(TokenError::Malformed)?;
+        let signature = base64::decode_config(signature, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| TokenError::Malformed)?;
+        // The signature is checked before anything else, in constant time.
+        self.mac(encoded_claims)
+            .verify_slice(&signature)
+            .map_err(|_| TokenError::InvalidSignature)?;
+
+        let claims = base64::decode_config(encoded_claims, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| TokenError::Malformed)?;
+        let claims: Claims = serde_json::from_slice(&claims).map_err(|_| TokenError::Malformed)?;
+
+        if claims.purpose != purpose {
+            return Err(TokenError::WrongPurpose);
+        }
+        if claims.expires_at <= now.timestamp() {
+            return Err(TokenError::Expired);
+        }
+
+        Ok(claims.user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_signer(secret: &str) -> TokenSigner {
+        TokenSigner::new(&TokenSettings {
+            secret: secret.to_string(),
+            verify_ttl_hours: 72,
+            unsubscribe_ttl_hours: 2160,
+            manage_ttl_hours: 24,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tokens_are_scoped_and_expire() {
+        let signer = new_signer("a secret which is at least 32 bytes long");
+        let user_id = Uuid::new_v4();
+        let now = Utc::now();
+        let token = signer.sign_at(TokenPurpose::Manage, &user_id, now);
+
+        assert_eq!(
+            signer.verify_at(&token, TokenPurpose::Manage, now).unwrap(),
+            user_id
+        );
+        assert!(matches!(
+            signer.verify_at(&token, TokenPurpose::Unsubscribe, now),
+            Err(TokenError::WrongPurpose)
+        ));
+        assert!(matches!(
+            signer.verify_at(&token, TokenPurpose::Manage, now + Duration::hours(24)),
+            Err(TokenError::Expired)
+        ));
+    }
+
+    #[test]
+    fn test_tokens_are_tamper_proof() {
+        let signer = new_signer("a secret which is at least 32 bytes long");
+        let token = signer.sign(TokenPurpose::Verify, &Uuid::new_v4());
+
+        let other_signer = new_signer("a different secret, also 32 bytes long");
+        assert!(matches!(
+            other_signer.verify(&token, TokenPurpose::Verify),
+            Err(TokenError::InvalidSignature)
+        ));
+
+        let (_, signature) = token.split_once('.').unwrap();
+        let claims = serde_json::to_vec(&Claims {
+            purpose: TokenPurpose::Verify,
+            user_id: Uuid::new_v4(),
+            expires_at: i64::MAX,
+        })
+        .unwrap();
+        let forged = format!(
+            "{}.{signature}",
+            base64::encode_config(claims, base64::URL_SAFE_NO_PAD)
+        );
+        assert!(matches!(
+            signer.verify(&forged, TokenPurpose::Verify),
+            Err(TokenError::InvalidSignature)
+        ));
+
+        assert!(signer.verify("not a token", TokenPurpose::Verify).is_err());
+    }
+}
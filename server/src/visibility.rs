@@ -0,0 +1,89 @@
+//! A single score, from 0 to 100, of how likely an aurora is to be seen from a
+//! location, combining the geomagnetic activity with the local conditions.
+
+use crate::astronomy::{Darkness, MoonIllumination, Sky};
+use crate::common::AlertLevel;
+
+/// How likely an aurora is to be bright enough to see, at each alert level.
+fn activity_factor(alert_level: AlertLevel) -> f64 {
+    match alert_level {
+        AlertLevel::Green => 0.0,
+        AlertLevel::Yellow => 0.4,
+        AlertLevel::Amber => 0.7,
+        AlertLevel::Red => 1.0,
+    }
+}
+
+fn cloud_factor(cloud_cover: i16) -> f64 {
+    1.0 - f64::from(cloud_cover.clamp(0, 100)) / 100.0
+}
+
+fn darkness_factor(darkness: Darkness) -> f64 {
+    match darkness {
+        Darkness::Day => 0.0,
+        Darkness::CivilTwilight => 0.2,
+        Darkness::NauticalTwilight => 0.6,
+        Darkness::AstronomicalTwilight => 0.9,
+        Darkness::Night => 1.0,
+    }
+}
+
+/// A full moon above the horizon washes out fainter aurorae, but never hides
+/// them entirely.
+fn moon_factor(moon: &MoonIllumination, moon_altitude: i16) -> f64 {
+    if moon_altitude > 0 {
+        1.0 - 0.5 * moon.fraction
+    } else {
+        1.0
+    }
+}
+
+/// Score the visibility of an aurora from a location.
+///
+/// Where the state of the `sky` isn't known, because the location has no
+/// coordinates, it is assumed to be dark and moonless.
+pub fn visibility_score(
+    alert_level: AlertLevel,
+    cloud_cover: i16,
+    sky: Option<&Sky>,
+    moon: &MoonIllumination,
+) -> i16 {
+    let sky_factor = match sky {
+        Some(sky) => darkness_factor(sky.darkness) * moon_factor(moon, sky.moon_altitude),
+        None => 1.0,
+    };
+    let score = 100.0 * activity_factor(alert_level) * cloud_factor(cloud_cover) * sky_factor;
+    score.round() as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visibility_score() {
+        let moon = MoonIllumination {
+            fraction: 1.0,
+            phase: 0.5,
+        };
+        let night_without_moon = Sky {
+            darkness: Darkness::Night,
+            dark_until: None,
+            moon_altitude: -10,
+        };
+        let night_with_moon = Sky {
+            moon_altitude: 30,
+            ..night_without_moon
+        };
+
+        assert_eq!(
+            visibility_score(AlertLevel::Red, 0, Some(&night_without_moon), &moon),
+            100
+        );
+        assert_eq!(
+            visibility_score(AlertLevel::Red, 50, Some(&night_with_moon), &moon),
+            25
+        );
+        assert_eq!(visibility_score(AlertLevel::Green, 0, None, &moon), 0);
+    }
+}